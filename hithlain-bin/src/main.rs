@@ -1,12 +1,151 @@
 use clap::{crate_authors, crate_description, crate_version};
-use clap::{App, Arg, SubCommand};
-use hithlain::error::NiceUnwrap;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use hithlain::analyze::depth::logic_depth;
+use hithlain::analyze::stats::design_stats;
+use hithlain::error::{NiceUnwrap, Warn};
 use hithlain::parse::desugar::desugar_program;
 use hithlain::parse::lexer::lex;
 use hithlain::parse::parser::Parser;
 use hithlain::parse::source::Source;
-use hithlain::sim::config::{SimulationConfig, VcdPath};
+use hithlain::sim::config::{DelayMode, Engine, SimulationConfig, VcdPath};
+use hithlain::sim::coverage::{ExpressionCoverage, ToggleCoverage};
+use hithlain::sim::glitch::GlitchDetector;
+use hithlain::sim::observer::ObserverFactory;
+use hithlain::sim::power::{PowerEstimate, PowerModel};
+use hithlain::sim::report::{CoverTable, Summary, TestReport};
 use hithlain::sim::Simulator;
+use hithlain::time::parse_timespec;
+use std::fs::File;
+use std::process::exit;
+use std::sync::Arc;
+
+fn delay_mode(args: &ArgMatches) -> DelayMode {
+    if args.is_present("transport") {
+        DelayMode::Transport
+    } else {
+        DelayMode::Inertial
+    }
+}
+
+fn engine(args: &ArgMatches) -> Engine {
+    if args.is_present("compiled") {
+        Engine::Compiled
+    } else {
+        Engine::EventDriven
+    }
+}
+
+fn jobs(args: &ArgMatches) -> Option<usize> {
+    args.value_of("jobs")
+        .map(|i| i.parse().expect("validated by clap"))
+}
+
+fn shuffle_seed(args: &ArgMatches) -> Option<u64> {
+    args.value_of("shuffle-seed")
+        .map(|i| i.parse().expect("validated by clap"))
+}
+
+fn max_deltas(args: &ArgMatches) -> Option<u64> {
+    args.value_of("max-deltas")
+        .map(|i| i.parse().expect("validated by clap"))
+}
+
+fn positive_number(i: String) -> Result<(), String> {
+    match i.parse::<f64>() {
+        Ok(n) if n >= 0.0 => Ok(()),
+        Ok(_) => Err("must not be negative".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn power_model(args: &ArgMatches) -> PowerModel {
+    let number = |name| {
+        args.value_of(name)
+            .map(|i: &str| i.parse::<f64>().expect("validated by clap"))
+    };
+
+    let default = PowerModel::default();
+    PowerModel {
+        voltage: number("voltage").unwrap_or(default.voltage),
+        capacitance: number("capacitance").unwrap_or(default.capacitance),
+        load: number("load").unwrap_or(default.load),
+        nets: args
+            .values_of("net-capacitance")
+            .into_iter()
+            .flatten()
+            .filter_map(|i| i.split_once('='))
+            .map(|(net, c)| (net.to_string(), c.parse().expect("validated by clap")))
+            .collect(),
+    }
+}
+
+fn is_u64(i: String) -> Result<(), String> {
+    i.parse::<u64>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// The arguments that change how `simulate` and `test` simulate.
+fn simulation_args() -> [Arg<'static, 'static>; 5] {
+    [
+        Arg::with_name("max-deltas")
+            .long("max-deltas")
+            .takes_value(true)
+            .validator(is_u64)
+            .help("How many delta cycles a single time step may take before signals are considered to oscillate"),
+        Arg::with_name("transport")
+            .long("transport")
+            .help("Use transport delays, which don't swallow pulses shorter than the delay"),
+        Arg::with_name("compiled")
+            .long("compiled")
+            .help("Evaluate combinational logic in a compiled, levelised netlist instead of through events"),
+        Arg::with_name("shuffle-seed")
+            .long("shuffle-seed")
+            .takes_value(true)
+            .validator(is_u64)
+            .help("Run events at the same time in a random order generated from this seed"),
+        Arg::with_name("glitches")
+            .long("glitches")
+            .help("Warn about every gate in a circuit whose outcome changed more than once in a single time step"),
+    ]
+}
+
+/// Prints every report with its diagnostics, then a summary. Returns whether all of them passed.
+fn print_reports(reports: &[TestReport]) -> bool {
+    for i in reports {
+        println!("{}", i);
+        i.warnings().iter().for_each(Warn::warn);
+        if let Some(e) = i.failure() {
+            e.warn();
+        }
+    }
+
+    let summary = Summary::new(reports);
+    println!("\n{}", summary);
+    summary.success()
+}
+
+/// Prints every signal that didn't toggle and every expression that wasn't both 0 and 1,
+/// and how many did.
+fn print_coverage(toggles: &ToggleCoverage, expressions: &ExpressionCoverage) {
+    println!();
+    for i in toggles.signals().iter().filter(|i| !i.toggled()) {
+        println!("{}", i);
+    }
+    expressions.uncovered().iter().for_each(Warn::warn);
+
+    println!("{}", toggles.summary());
+    println!("{}", expressions.summary());
+}
+
+/// Prints how often every cover statement was hit. Returns whether all of them were.
+fn print_covers(reports: &[TestReport]) -> bool {
+    let table = CoverTable::new(reports);
+    if table.is_empty() {
+        return true;
+    }
+
+    println!("\n{}", table);
+    table.never_hit().is_empty()
+}
 
 fn main() {
     let matches = App::new("Hithlain")
@@ -45,20 +184,24 @@ fn main() {
                 Arg::with_name("time")
                     .long("time")
                     .short("t")
+                    .takes_value(true)
                     .help("How long to simulate for. (number followed by time unit in [ns, us, ms, s]). Leave empty to run to completion.")
             )
             .arg(
                 Arg::with_name("overshoot")
                     .long("overshoot")
+                    .takes_value(true)
                     .help("Time buffer to add to the end of the generated vcd (number followed by time unit in [ns, us, ms, s])")
             )
             .arg(
                 Arg::with_name("output")
                     .short("o")
-                    .short("output")
+                    .long("output")
+                    .takes_value(true)
                     .help("File to output vcd to. Prints to stdout when `-` is provided")
                     .default_value("output.vcd")
             )
+            .args(&simulation_args())
         ).subcommand(
         SubCommand::with_name("test")
                 .alias("t")
@@ -66,6 +209,154 @@ fn main() {
                     Arg::with_name("name")
                         .takes_value(true)
                 )
+                .arg(
+                    Arg::with_name("fail-fast")
+                        .long("fail-fast")
+                        .help("Stop at the first failing assertion instead of reporting all failures")
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .long("jobs")
+                        .short("j")
+                        .takes_value(true)
+                        .validator(|i| match i.parse::<usize>() {
+                            Ok(0) => Err("must be at least 1".to_string()),
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(e.to_string()),
+                        })
+                        .help("How many tests to simulate at the same time")
+                )
+                .arg(
+                    Arg::with_name("vcd-dir")
+                        .long("vcd-dir")
+                        .takes_value(true)
+                        .help("Directory to write a vcd file for every test to, named after the test")
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only run tests with this tag. Can be given more than once.")
+                )
+                .args(&simulation_args())
+                .arg(
+                    Arg::with_name("coverage")
+                        .long("coverage")
+                        .help("Report every signal that didn't both rise and fall, and every expression in a circuit that wasn't both 0 and 1 during the tests")
+                )
+                .arg(
+                    Arg::with_name("lcov")
+                        .long("lcov")
+                        .takes_value(true)
+                        .help("File to write the toggle coverage to, in the LCOV format. Implies --coverage")
+                )
+                .arg(
+                    Arg::with_name("power")
+                        .long("power")
+                        .help("Count how often every net in a circuit toggles, and estimate the dynamic power of every test from that")
+                )
+                .arg(
+                    Arg::with_name("voltage")
+                        .long("voltage")
+                        .takes_value(true)
+                        .validator(positive_number)
+                        .help("The supply voltage the power is estimated with, in volts. Implies --power")
+                )
+                .arg(
+                    Arg::with_name("capacitance")
+                        .long("capacitance")
+                        .takes_value(true)
+                        .validator(positive_number)
+                        .help("The capacitance of every net, in femtofarads. Implies --power")
+                )
+                .arg(
+                    Arg::with_name("load")
+                        .long("load")
+                        .takes_value(true)
+                        .validator(positive_number)
+                        .help("The capacitance every gate input adds to the net that drives it, in femtofarads. Implies --power")
+                )
+                .arg(
+                    Arg::with_name("net-capacitance")
+                        .long("net-capacitance")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .validator(|i| match i.split_once('=') {
+                            Some((_, c)) => positive_number(c.to_string()),
+                            None => Err("expected a net and its capacitance, like add.c_out=2.5".to_string()),
+                        })
+                        .help("The capacitance of a single net in femtofarads, like add.c_out=2.5. Can be given more than once. Implies --power")
+                )
+                .arg(
+                    Arg::with_name("faults")
+                        .long("faults")
+                        .conflicts_with("name")
+                        .help("Afterwards, inject every signal in a circuit with a stuck-at-0 and a stuck-at-1 fault, one at a time, and report which faults the tests missed")
+                )
+                .arg(
+                    Arg::with_name("fail-uncovered")
+                        .long("fail-uncovered")
+                        .help("Fail when a cover statement was never hit")
+                )
+                .arg(
+                    Arg::with_name("detect-races")
+                        .long("detect-races")
+                        .help("Run every test again with events at the same time in reverse order, and fail when that changes any signal")
+                )
+        ).subcommand(
+        SubCommand::with_name("check")
+                .alias("c")
+                .arg(
+                    Arg::with_name("circuit")
+                        .takes_value(true)
+                        .help("The circuit to exhaustively check. Checks all circuits when omitted.")
+                )
+                .arg(
+                    Arg::with_name("fail-fast")
+                        .long("fail-fast")
+                        .help("Stop at the first failing check")
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .long("jobs")
+                        .short("j")
+                        .takes_value(true)
+                        .validator(|i| match i.parse::<usize>() {
+                            Ok(0) => Err("must be at least 1".to_string()),
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(e.to_string()),
+                        })
+                        .help("How many checks to run at the same time")
+                )
+        ).subcommand(
+        SubCommand::with_name("stats")
+                .arg(
+                    Arg::with_name("circuit")
+                        .takes_value(true)
+                        .help("The top level circuit. Every circuit that isn't used by another one is a top level when omitted.")
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                )
+        ).subcommand(
+        SubCommand::with_name("analyze")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("depth")
+                        .about("Prints the longest chain of gates from any input to every output of a circuit, through all of its instances")
+                        .arg(
+                            Arg::with_name("circuit")
+                                .required(true)
+                        )
+                )
         )
         .get_matches();
 
@@ -81,25 +372,159 @@ fn main() {
         ("simulate", Some(args)) => {
             let entrypoint = args.value_of("entry").expect("entry point required");
 
+            let glitches = GlitchDetector::new();
+
+            let default = SimulationConfig::default();
             let cfg = SimulationConfig {
                 create_vcd: true,
                 vcd_path: VcdPath::Path(args.value_of("output").expect("has default").into()),
-                ..SimulationConfig::default()
+                simulation_time: args
+                    .value_of("time")
+                    .map(parse_timespec)
+                    .transpose()
+                    .nice_unwrap(),
+                vcd_overshoot_duration: args
+                    .value_of("overshoot")
+                    .map(parse_timespec)
+                    .transpose()
+                    .nice_unwrap()
+                    .unwrap_or(default.vcd_overshoot_duration),
+                max_deltas: max_deltas(args).unwrap_or(default.max_deltas),
+                delay_mode: delay_mode(args),
+                engine: engine(args),
+                shuffle_seed: shuffle_seed(args),
+                observers: if args.is_present("glitches") {
+                    vec![Arc::new(glitches.clone())]
+                } else {
+                    vec![]
+                },
+                ..default
             };
 
             let sim = Simulator::new(desugared, cfg).nice_unwrap();
-            sim.run_test(entrypoint).nice_unwrap();
+            let report = sim.run_test(entrypoint).nice_unwrap();
+            report.warnings().iter().for_each(Warn::warn);
+            glitches.warnings().iter().for_each(Warn::warn);
+            report.into_result().nice_unwrap();
         }
         ("test", Some(args)) => {
-            let cfg = SimulationConfig::default();
+            let coverage = ToggleCoverage::new();
+            let expressions = ExpressionCoverage::new();
+            let glitches = GlitchDetector::new();
+            let with_coverage = args.is_present("coverage") || args.is_present("lcov");
+
+            let mut observers: Vec<Arc<dyn ObserverFactory>> = Vec::new();
+            if with_coverage {
+                observers.push(Arc::new(coverage.clone()));
+                observers.push(Arc::new(expressions.clone()));
+            }
+            if args.is_present("glitches") {
+                observers.push(Arc::new(glitches.clone()));
+            }
+            let power = PowerEstimate::new(power_model(args));
+            let with_power = ["power", "voltage", "capacitance", "load", "net-capacitance"]
+                .iter()
+                .any(|i| args.is_present(i));
+            if with_power {
+                observers.push(Arc::new(power.clone()));
+            }
+
+            let default = SimulationConfig::default();
+            let cfg = SimulationConfig {
+                fail_fast: args.is_present("fail-fast"),
+                vcd_path: args
+                    .value_of("vcd-dir")
+                    .map_or(VcdPath::InMemory, |i| VcdPath::Directory(i.into())),
+                jobs: jobs(args).unwrap_or(default.jobs),
+                max_deltas: max_deltas(args).unwrap_or(default.max_deltas),
+                delay_mode: delay_mode(args),
+                engine: engine(args),
+                shuffle_seed: shuffle_seed(args),
+                detect_races: args.is_present("detect-races"),
+                observers,
+                ..default
+            };
+            let sim = Simulator::new(desugared, cfg).nice_unwrap();
+
+            let tags: Vec<_> = args
+                .values_of("tag")
+                .map(|i| i.map(ToString::to_string).collect())
+                .unwrap_or_default();
+            let reports = if let Some(test_name) = args.value_of("name") {
+                vec![sim.run_test(test_name).nice_unwrap()]
+            } else if !tags.is_empty() {
+                sim.run_tests(&tags)
+            } else {
+                let mut reports = sim.run_tests(&[]);
+                if !args.is_present("fail-fast") || Summary::new(&reports).success() {
+                    reports.extend(sim.run_checks());
+                }
+                reports
+            };
+            let success = print_reports(&reports);
+            glitches.warnings().iter().for_each(Warn::warn);
+            let covered = print_covers(&reports);
+
+            if with_coverage {
+                print_coverage(&coverage, &expressions);
+            }
+            if let Some(path) = args.value_of("lcov") {
+                let mut f = File::create(path).unwrap_or_else(|e| {
+                    eprintln!("couldn't create {}: {}", path, e);
+                    exit(1);
+                });
+                if let Err(e) = coverage.write_lcov(&mut f) {
+                    eprintln!("couldn't write {}: {}", path, e);
+                    exit(1);
+                }
+            }
+            for i in power.tests() {
+                println!("\n{}", i);
+            }
+            if args.is_present("faults") {
+                println!("\n{}", sim.fault_simulation(&reports));
+            }
+
+            if !success || (!covered && args.is_present("fail-uncovered")) {
+                exit(1);
+            }
+        }
+        ("check", Some(args)) => {
+            let default = SimulationConfig::default();
+            let cfg = SimulationConfig {
+                fail_fast: args.is_present("fail-fast"),
+                jobs: jobs(args).unwrap_or(default.jobs),
+                ..default
+            };
             let sim = Simulator::new(desugared, cfg).nice_unwrap();
 
-            if let Some(test_name) = args.value_of("test") {
-                sim.run_test(test_name).nice_unwrap();
+            if let Some(circuit) = args.value_of("circuit") {
+                sim.run_check(circuit).nice_unwrap();
             } else {
-                sim.run_all_tests().nice_unwrap();
+                if !print_reports(&sim.run_checks()) {
+                    exit(1);
+                }
             }
         }
+        ("stats", Some(args)) => {
+            let stats = design_stats(&desugared, args.value_of("circuit")).nice_unwrap();
+
+            if args.value_of("format") == Some("json") {
+                if let Err(e) = stats.write_json(&mut std::io::stdout()) {
+                    eprintln!("couldn't write statistics: {}", e);
+                    exit(1);
+                }
+            } else {
+                print!("{}", stats);
+            }
+        }
+        ("analyze", Some(args)) => match args.subcommand() {
+            ("depth", Some(args)) => {
+                let circuit = args.value_of("circuit").expect("circuit required");
+                print!("{}", logic_depth(&desugared, circuit).nice_unwrap());
+            }
+            (s, _) => unreachable!("no such analysis: {}", s),
+        },
         (s, _) => unreachable!("no such subcommand: {}", s),
     }
 }
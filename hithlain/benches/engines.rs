@@ -0,0 +1,62 @@
+//! Compares the event driven and compiled engines on a large generated ripple carry adder.
+//! Run with `cargo bench -p hithlain`.
+
+#[path = "../src/generate.rs"]
+mod generate;
+
+use generate::ripple_carry_adder;
+use hithlain::error::NiceUnwrap;
+use hithlain::parse::desugar::desugar_program;
+use hithlain::parse::lexer::lex;
+use hithlain::parse::parser::Parser;
+use hithlain::parse::source::Source;
+use hithlain::sim::config::{Engine, SimulationConfig};
+use hithlain::sim::Simulator;
+use std::time::Instant;
+
+const BITS: usize = 256;
+const VECTORS: usize = 200;
+
+fn run(src: &str, inputs: &[String], engine: Engine) {
+    let lexed = lex(&Source::new(src, "ripple.hl")).nice_unwrap_panic();
+    let parsed = Parser::new(lexed).parse_program().nice_unwrap_panic();
+    let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+    let config = SimulationConfig {
+        create_vcd: false,
+        engine,
+        // the carry chain takes a few delta cycles per bit in the event driven engine
+        max_deltas: 100 * BITS as u64,
+        ..SimulationConfig::default()
+    };
+    let simulator = Simulator::new(desugared, config).nice_unwrap_panic();
+
+    let start = Instant::now();
+    let mut sim = simulator.instantiate("ripple").nice_unwrap_panic();
+    let mut seed = 0x2545_f491_u32;
+    for _ in 0..VECTORS {
+        for input in inputs {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+
+            sim.poke(input, seed & 1 == 1).nice_unwrap_panic();
+        }
+        sim.settle().nice_unwrap_panic();
+    }
+
+    println!(
+        "{:?}: {} vectors through a {} bit adder in {:?}",
+        engine,
+        VECTORS,
+        BITS,
+        start.elapsed()
+    );
+}
+
+fn main() {
+    let (src, inputs) = ripple_carry_adder(BITS);
+
+    run(&src, &inputs, Engine::EventDriven);
+    run(&src, &inputs, Engine::Compiled);
+}
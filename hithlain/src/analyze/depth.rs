@@ -0,0 +1,326 @@
+use crate::analyze::find_circuit;
+use crate::parse::desugared_ast::Program;
+use crate::parse::span::Span;
+use crate::sim::instantiate::{instantiate_circuit, UniqueVariableRef, UniqueVariableRefGenerator};
+use crate::sim::instantiated_ast::{BinaryBuiltin, Statement};
+use crate::sim::interactive::UnknownCircuit;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// What writes a signal, after flattening every instance.
+enum Driver {
+    Gate(Vec<UniqueVariableRef>),
+    /// moves between instances don't add any depth
+    Wire(UniqueVariableRef),
+    Constant,
+}
+
+#[allow(clippy::mutable_key_type)]
+fn find_drivers(body: &[Statement], drivers: &mut HashMap<UniqueVariableRef, Driver>) {
+    for i in body {
+        match i {
+            Statement::Not { input, into } => {
+                drivers.insert(into.clone(), Driver::Gate(vec![input.clone()]));
+            }
+            Statement::And(BinaryBuiltin { a, b, into })
+            | Statement::Or(BinaryBuiltin { a, b, into })
+            | Statement::Nand(BinaryBuiltin { a, b, into })
+            | Statement::Nor(BinaryBuiltin { a, b, into })
+            | Statement::Xor(BinaryBuiltin { a, b, into })
+            | Statement::Xnor(BinaryBuiltin { a, b, into }) => {
+                drivers.insert(into.clone(), Driver::Gate(vec![a.clone(), b.clone()]));
+            }
+            Statement::Move(a, b) => {
+                drivers.insert(a.clone(), Driver::Wire(b.clone()));
+            }
+            Statement::Set(a, _) | Statement::Force(a, _) => {
+                drivers.insert(a.clone(), Driver::Constant);
+            }
+            Statement::Delayed(s, _) => find_drivers(std::slice::from_ref(s), drivers),
+            Statement::CreateCircuitInstance(c) => find_drivers(&c.body, drivers),
+            Statement::Assert(..) | Statement::TemporalAssert(..) | Statement::Cover(..) => {}
+        }
+    }
+}
+
+/// Longest paths through the drivers, remembering where every one came from.
+struct Paths {
+    drivers: HashMap<UniqueVariableRef, Driver>,
+    /// the depth of a signal, and the input of its driver the longest path goes through
+    depths: HashMap<UniqueVariableRef, (usize, Option<UniqueVariableRef>)>,
+    on_stack: HashSet<UniqueVariableRef>,
+}
+
+impl Paths {
+    fn depth(&mut self, v: &UniqueVariableRef) -> usize {
+        if let Some((depth, _)) = self.depths.get(v) {
+            return *depth;
+        }
+        // only a loop through a delay gets here, it is cut where it was entered
+        if !self.on_stack.insert(v.clone()) {
+            return 0;
+        }
+
+        let (inputs, gates) = match self.drivers.get(v) {
+            Some(Driver::Gate(inputs)) => (inputs.clone(), 1),
+            Some(Driver::Wire(from)) => (vec![from.clone()], 0),
+            Some(Driver::Constant) | None => (vec![], 0),
+        };
+
+        let mut longest = None;
+        for i in inputs {
+            let depth = self.depth(&i);
+            if longest.as_ref().is_none_or(|(d, _)| depth > *d) {
+                longest = Some((depth, i));
+            }
+        }
+
+        self.on_stack.remove(v);
+        let res = match longest {
+            Some((depth, from)) => (depth + gates, Some(from)),
+            None => (0, None),
+        };
+        let depth = res.0;
+        self.depths.insert(v.clone(), res);
+        depth
+    }
+
+    /// The signals on the longest path to `v`, starting at an input (or constant).
+    /// Only the outcomes of gates are included, not the wires between instances.
+    #[allow(clippy::mutable_key_type)]
+    fn path(&self, v: &UniqueVariableRef) -> Vec<PathStep> {
+        let mut res = Vec::new();
+        let mut current = Some(v.clone());
+        let mut seen = HashSet::new();
+
+        while let Some(v) = current {
+            if !seen.insert(v.clone()) {
+                break;
+            }
+
+            let from = self.depths.get(&v).and_then(|(_, from)| from.clone());
+            let is_gate = matches!(self.drivers.get(&v), Some(Driver::Gate(_)));
+            if is_gate || from.is_none() {
+                res.push(PathStep {
+                    signal: (!v.generated).then(|| v.name()),
+                    span: v.gate().cloned(),
+                });
+            }
+
+            current = from;
+        }
+
+        res.reverse();
+        res
+    }
+}
+
+/// One signal on a path through a circuit.
+#[derive(Debug, Clone)]
+pub struct PathStep {
+    /// `None` for the parts of an expression
+    pub(crate) signal: Option<String>,
+    /// the gate expression that computes it, `None` for the input the path starts at
+    pub(crate) span: Option<Span>,
+}
+
+impl PathStep {
+    #[must_use]
+    pub fn signal(&self) -> Option<&str> {
+        self.signal.as_deref()
+    }
+
+    #[must_use]
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+}
+
+impl Display for PathStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.span, &self.signal) {
+            (Some(span), signal) => {
+                write!(f, "{}", span.text())?;
+                if let Some(signal) = signal {
+                    write!(f, " -> {}", signal)?;
+                }
+                write!(f, " ({}:{})", span.source().name(), span.line())
+            }
+            (None, Some(signal)) => write!(f, "{}", signal),
+            (None, None) => write!(f, "constant"),
+        }
+    }
+}
+
+/// The longest chain of gates from any input to one output.
+#[derive(Debug, Clone)]
+pub struct OutputDepth {
+    pub(crate) output: String,
+    pub(crate) depth: usize,
+    pub(crate) path: Vec<PathStep>,
+}
+
+impl OutputDepth {
+    #[must_use]
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// The number of gates on the longest path.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &[PathStep] {
+        &self.path
+    }
+}
+
+/// The logic depth of every output of a circuit, through all of its instances.
+#[derive(Debug, Clone)]
+pub struct DepthReport {
+    pub(crate) circuit: String,
+    pub(crate) outputs: Vec<OutputDepth>,
+}
+
+impl DepthReport {
+    #[must_use]
+    pub fn circuit(&self) -> &str {
+        &self.circuit
+    }
+
+    /// Every output, in the order they are declared in.
+    #[must_use]
+    pub fn outputs(&self) -> &[OutputDepth] {
+        &self.outputs
+    }
+
+    /// The output with the longest path, the first one when there are several.
+    #[must_use]
+    pub fn critical(&self) -> Option<&OutputDepth> {
+        self.outputs
+            .iter()
+            .fold(None, |longest: Option<&OutputDepth>, i| match longest {
+                Some(l) if l.depth >= i.depth => Some(l),
+                _ => Some(i),
+            })
+    }
+}
+
+impl Display for DepthReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "logic depth of {}", self.circuit)?;
+        for i in &self.outputs {
+            writeln!(f, "  {}: {} gate(s)", i.output, i.depth)?;
+        }
+
+        if let Some(critical) = self.critical() {
+            writeln!(
+                f,
+                "\ncritical path, to {} ({} gate(s)):",
+                critical.output, critical.depth
+            )?;
+            for i in &critical.path {
+                writeln!(f, "  {}", i)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the longest chain of gates from any input to every output of a circuit.
+/// Delays aren't taken into account, every gate counts once.
+pub fn logic_depth(program: &Program, circuit: &str) -> Result<DepthReport, UnknownCircuit> {
+    let circuit = find_circuit(program, circuit)?;
+    let instantiated = instantiate_circuit(circuit, &mut UniqueVariableRefGenerator::new(), vec![]);
+
+    let mut paths = Paths {
+        drivers: HashMap::new(),
+        depths: HashMap::new(),
+        on_stack: HashSet::new(),
+    };
+    find_drivers(&instantiated.body, &mut paths.drivers);
+
+    let outputs = instantiated
+        .outputs
+        .iter()
+        .map(|i| OutputDepth {
+            output: i.name(),
+            depth: paths.depth(i),
+            path: paths.path(i),
+        })
+        .collect();
+
+    Ok(DepthReport {
+        circuit: circuit.name.0.clone(),
+        outputs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::logic_depth;
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+
+    #[test]
+    fn ripple_carry() {
+        let src = "
+circuit add: a b c_in -> o c_out {
+    o = a xor b xor c_in;
+    c_out = (a and b) or ((a xor b) and c_in);
+}
+
+circuit add2: a0 a1 b0 b1 c_in -> o0 o1 c_out {
+    o0, c0 = add(a0, b0, c_in);
+    o1, c_out = add(a1, b1, c0);
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+        let program =
+            desugar_program(&parser.parse_program().nice_unwrap_panic()).nice_unwrap_panic();
+
+        let report = logic_depth(&program, "add").nice_unwrap_panic();
+        let depths: Vec<_> = report
+            .outputs()
+            .iter()
+            .map(|i| (i.output(), i.depth()))
+            .collect();
+        assert_eq!(depths, vec![("add.o", 2), ("add.c_out", 3)]);
+
+        let path: Vec<_> = report
+            .critical()
+            .unwrap()
+            .path()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            path,
+            vec![
+                "add.a",
+                "a xor b (test:4)",
+                "(a xor b) and c_in (test:4)",
+                "(a and b) or ((a xor b) and c_in) -> add.c_out (test:4)",
+            ]
+        );
+
+        // the carry ripples through both instances
+        let report = logic_depth(&program, "add2").nice_unwrap_panic();
+        let depths: Vec<_> = report.outputs().iter().map(|i| i.depth()).collect();
+        assert_eq!(depths, vec![2, 4, 5]);
+        let critical = report.critical().unwrap();
+        assert_eq!(critical.output(), "add2.c_out");
+        assert_eq!(critical.path().len(), 6);
+
+        assert!(logic_depth(&program, "sub").is_err());
+    }
+}
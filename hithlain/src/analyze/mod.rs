@@ -0,0 +1,26 @@
+use crate::parse::desugared_ast::{Circuit, Program};
+use crate::sim::interactive::UnknownCircuit;
+use std::sync::Arc;
+
+pub mod depth;
+pub mod stats;
+
+/// Finds a circuit by name.
+pub(crate) fn find_circuit<'p>(
+    program: &'p Program,
+    name: &str,
+) -> Result<&'p Arc<Circuit>, UnknownCircuit> {
+    program
+        .circuits
+        .iter()
+        .find(|i| i.name.0 == name)
+        .ok_or_else(|| UnknownCircuit {
+            name: name.to_string(),
+            available: program
+                .circuits
+                .iter()
+                .map(|i| i.name.0.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+}
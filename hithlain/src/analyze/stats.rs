@@ -0,0 +1,531 @@
+use crate::analyze::find_circuit;
+use crate::parse::desugared_ast as d;
+use crate::parse::desugared_ast::Program;
+use crate::sim::instantiate::{instantiate_circuit, UniqueVariableRef, UniqueVariableRefGenerator};
+use crate::sim::instantiated_ast as inst;
+use crate::sim::interactive::UnknownCircuit;
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+/// How many signals are listed with their fan-out.
+const HIGHEST_FAN_OUT: usize = 5;
+
+const GATES: [&str; 7] = ["not", "and", "or", "nand", "nor", "xor", "xnor"];
+
+/// The number of every builtin gate, in the order of [`GATES`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct GateCounts([usize; 7]);
+
+impl GateCounts {
+    fn add(&mut self, gate: &str) {
+        if let Some(index) = GATES.iter().position(|i| *i == gate) {
+            self.0[index] += 1;
+        }
+    }
+
+    fn add_desugared(&mut self, body: &[d::Statement]) {
+        for i in body {
+            match i {
+                d::Statement::Not { .. } => self.add("not"),
+                d::Statement::And(_) => self.add("and"),
+                d::Statement::Or(_) => self.add("or"),
+                d::Statement::Nand(_) => self.add("nand"),
+                d::Statement::Nor(_) => self.add("nor"),
+                d::Statement::Xor(_) => self.add("xor"),
+                d::Statement::Xnor(_) => self.add("xnor"),
+                d::Statement::Delayed(s, _) => self.add_desugared(std::slice::from_ref(s)),
+                _ => {}
+            }
+        }
+    }
+
+    /// The number of gates with this name, like `and`.
+    #[must_use]
+    pub fn get(&self, gate: &str) -> usize {
+        GATES
+            .iter()
+            .position(|i| *i == gate)
+            .map_or(0, |index| self.0[index])
+    }
+
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    /// Every gate with its count, including the ones that don't occur.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        GATES.iter().copied().zip(self.0.iter().copied())
+    }
+}
+
+impl Serialize for GateCounts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(GATES.len()))?;
+        for (gate, count) in self.iter() {
+            map.serialize_entry(gate, &count)?;
+        }
+        map.end()
+    }
+}
+
+impl Display for GateCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let counts: Vec<_> = self
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(gate, count)| format!("{} {}", count, gate))
+            .collect();
+
+        if counts.is_empty() {
+            write!(f, "no gates")
+        } else {
+            write!(f, "{} gate(s): {}", self.total(), counts.join(", "))
+        }
+    }
+}
+
+/// What a single circuit contains itself, without looking inside its instances.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStats {
+    pub(crate) name: String,
+    pub(crate) gates: GateCounts,
+    /// the circuit every instance is of, in the order they are created
+    pub(crate) instances: Vec<String>,
+}
+
+impl CircuitStats {
+    fn new(circuit: &d::Circuit) -> Self {
+        let mut gates = GateCounts::default();
+        gates.add_desugared(&circuit.body);
+
+        Self {
+            name: circuit.name.0.clone(),
+            gates,
+            instances: circuit
+                .body
+                .iter()
+                .filter_map(|i| match i {
+                    d::Statement::Custom { circuit, .. } => Some(circuit.name.0.clone()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn gates(&self) -> GateCounts {
+        self.gates
+    }
+
+    #[must_use]
+    pub fn instances(&self) -> &[String] {
+        &self.instances
+    }
+}
+
+/// One instance in the hierarchy, with the instances inside it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Instance {
+    pub(crate) circuit: String,
+    #[serde(rename = "instances")]
+    pub(crate) children: Vec<Instance>,
+}
+
+impl Instance {
+    #[must_use]
+    pub fn circuit(&self) -> &str {
+        &self.circuit
+    }
+
+    #[must_use]
+    pub fn children(&self) -> &[Instance] {
+        &self.children
+    }
+
+    /// The number of instances inside this one, at any depth.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.children.iter().map(|i| i.count() + 1).sum()
+    }
+
+    fn write_tree(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.circuit, indent = indent)?;
+        for i in &self.children {
+            i.write_tree(f, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// A circuit with all of its instances flattened, as it would be simulated.
+#[derive(Debug, Clone)]
+pub struct TopLevelStats {
+    pub(crate) hierarchy: Instance,
+    pub(crate) gates: GateCounts,
+    /// the signals with the highest fan-out, highest first
+    pub(crate) fan_out: Vec<(String, usize)>,
+}
+
+impl TopLevelStats {
+    fn new(circuit: &inst::Circuit) -> Self {
+        let mut netlist = Flattened::default();
+        let hierarchy = netlist.add(circuit);
+
+        let mut fan_out: Vec<_> = netlist
+            .signals
+            .iter()
+            // a signal moved into an instance is part of the same net as where it came from
+            .filter(|i| !i.generated && !netlist.moved.contains(*i))
+            .map(|i| (i.name(), netlist.readers.fan_out(i)))
+            .collect();
+        fan_out.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        fan_out.truncate(HIGHEST_FAN_OUT);
+
+        Self {
+            hierarchy,
+            gates: netlist.gates,
+            fan_out,
+        }
+    }
+
+    #[must_use]
+    pub fn circuit(&self) -> &str {
+        &self.hierarchy.circuit
+    }
+
+    #[must_use]
+    pub fn hierarchy(&self) -> &Instance {
+        &self.hierarchy
+    }
+
+    /// Every gate in the circuit and its instances.
+    #[must_use]
+    pub fn gates(&self) -> GateCounts {
+        self.gates
+    }
+
+    /// The number of instances, not counting the circuit itself.
+    #[must_use]
+    pub fn instances(&self) -> usize {
+        self.hierarchy.count()
+    }
+
+    #[must_use]
+    pub fn max_fan_out(&self) -> usize {
+        self.fan_out.first().map_or(0, |(_, i)| *i)
+    }
+
+    /// The signals with the highest fan-out, highest first.
+    #[must_use]
+    pub fn fan_out(&self) -> &[(String, usize)] {
+        &self.fan_out
+    }
+}
+
+/// For every signal, the gates (`None`) and signals it is moved to that read it.
+#[derive(Default)]
+pub(crate) struct Readers(HashMap<UniqueVariableRef, Vec<Option<UniqueVariableRef>>>);
+
+impl Readers {
+    /// Adds a signal without readers. Returns whether it wasn't there yet.
+    fn insert(&mut self, v: &UniqueVariableRef) -> bool {
+        if self.0.contains_key(v) {
+            return false;
+        }
+        self.0.insert(v.clone(), Vec::new());
+        true
+    }
+
+    /// `from` is read by a gate, or moved into `by`.
+    pub(crate) fn read(&mut self, from: &UniqueVariableRef, by: Option<&UniqueVariableRef>) {
+        self.0.entry(from.clone()).or_default().push(by.cloned());
+    }
+
+    /// The number of gate inputs a signal drives, following it through moves into instances.
+    pub(crate) fn fan_out(&self, v: &UniqueVariableRef) -> usize {
+        self.fan_out_from(v, &mut HashSet::new())
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn fan_out_from(&self, v: &UniqueVariableRef, seen: &mut HashSet<UniqueVariableRef>) -> usize {
+        if !seen.insert(v.clone()) {
+            return 0;
+        }
+
+        self.0.get(v).map_or(0, |readers| {
+            readers
+                .iter()
+                .map(|i| match i {
+                    Some(into) => self.fan_out_from(into, seen),
+                    None => 1,
+                })
+                .sum()
+        })
+    }
+}
+
+impl Serialize for TopLevelStats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct FanOut<'a> {
+            signal: &'a str,
+            fan_out: usize,
+        }
+
+        let fan_out: Vec<_> = self
+            .fan_out
+            .iter()
+            .map(|(signal, fan_out)| FanOut {
+                signal,
+                fan_out: *fan_out,
+            })
+            .collect();
+
+        let mut s = serializer.serialize_struct("TopLevelStats", 6)?;
+        s.serialize_field("circuit", self.circuit())?;
+        s.serialize_field("gates", &self.gates)?;
+        s.serialize_field("instances", &self.instances())?;
+        s.serialize_field("hierarchy", &self.hierarchy)?;
+        s.serialize_field("max_fan_out", &self.max_fan_out())?;
+        s.serialize_field("fan_out", &fan_out)?;
+        s.end()
+    }
+}
+
+/// The gates and connections of a circuit with all instances flattened.
+#[derive(Default)]
+struct Flattened {
+    gates: GateCounts,
+    /// every signal, in the order it was first seen
+    signals: Vec<UniqueVariableRef>,
+    readers: Readers,
+    /// signals that are driven by a move
+    moved: HashSet<UniqueVariableRef>,
+}
+
+impl Flattened {
+    fn read(&mut self, from: &UniqueVariableRef, by: Option<&UniqueVariableRef>) {
+        for i in std::iter::once(from).chain(by) {
+            if self.readers.insert(i) {
+                self.signals.push(i.clone());
+            }
+        }
+        self.readers.read(from, by);
+    }
+
+    fn add(&mut self, circuit: &inst::Circuit) -> Instance {
+        let mut children = Vec::new();
+        self.add_statements(&circuit.body, &mut children);
+
+        Instance {
+            circuit: circuit.name.0.clone(),
+            children,
+        }
+    }
+
+    fn add_statements(&mut self, body: &[inst::Statement], children: &mut Vec<Instance>) {
+        for i in body {
+            let (gate, inputs) = match i {
+                inst::Statement::Not { input, .. } => ("not", vec![input]),
+                inst::Statement::And(b) => ("and", vec![&b.a, &b.b]),
+                inst::Statement::Or(b) => ("or", vec![&b.a, &b.b]),
+                inst::Statement::Nand(b) => ("nand", vec![&b.a, &b.b]),
+                inst::Statement::Nor(b) => ("nor", vec![&b.a, &b.b]),
+                inst::Statement::Xor(b) => ("xor", vec![&b.a, &b.b]),
+                inst::Statement::Xnor(b) => ("xnor", vec![&b.a, &b.b]),
+                inst::Statement::Move(a, b) => {
+                    self.read(b, Some(a));
+                    self.moved.insert(a.clone());
+                    continue;
+                }
+                inst::Statement::Delayed(s, _) => {
+                    self.add_statements(std::slice::from_ref(s), children);
+                    continue;
+                }
+                inst::Statement::CreateCircuitInstance(c) => {
+                    children.push(self.add(c));
+                    continue;
+                }
+                _ => continue,
+            };
+
+            self.gates.add(gate);
+            for input in inputs {
+                self.read(input, None);
+            }
+        }
+    }
+}
+
+/// The size of a design: what every circuit contains, and the totals for every top level circuit.
+#[derive(Debug, Clone, Serialize)]
+pub struct DesignStats {
+    pub(crate) circuits: Vec<CircuitStats>,
+    pub(crate) top_levels: Vec<TopLevelStats>,
+}
+
+impl DesignStats {
+    #[must_use]
+    pub fn circuits(&self) -> &[CircuitStats] {
+        &self.circuits
+    }
+
+    #[must_use]
+    pub fn top_levels(&self) -> &[TopLevelStats] {
+        &self.top_levels
+    }
+
+    /// Writes the statistics as a single JSON object.
+    pub fn write_json(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        serde_json::to_writer(&mut *w, self)?;
+        writeln!(w)
+    }
+}
+
+impl Display for DesignStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for i in &self.circuits {
+            write!(f, "circuit {}: {}", i.name, i.gates)?;
+            if !i.instances.is_empty() {
+                write!(f, "; instances: {}", i.instances.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+
+        for i in &self.top_levels {
+            writeln!(f, "\n{}, with every instance:", i.circuit())?;
+            writeln!(f, "  {}", i.gates)?;
+            writeln!(f, "  {} instance(s):", i.instances())?;
+            i.hierarchy.write_tree(f, 4)?;
+            writeln!(f, "  max fan-out: {}", i.max_fan_out())?;
+            for (signal, fan_out) in &i.fan_out {
+                writeln!(f, "    {}: {}", signal, fan_out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the statistics of a design. With a circuit, only that circuit and the circuits it uses
+/// are included, with it as the top level. Otherwise every circuit that isn't used by another
+/// one is a top level.
+pub fn design_stats(program: &Program, top: Option<&str>) -> Result<DesignStats, UnknownCircuit> {
+    let circuits: Vec<_> = program
+        .circuits
+        .iter()
+        .map(|i| CircuitStats::new(i))
+        .collect();
+
+    let top_levels: Vec<_> = match top {
+        Some(name) => vec![find_circuit(program, name)?.clone()],
+        None => {
+            let instantiated: HashSet<_> = circuits.iter().flat_map(|i| &i.instances).collect();
+            program
+                .circuits
+                .iter()
+                .filter(|c| !instantiated.contains(&c.name.0))
+                .cloned()
+                .collect()
+        }
+    };
+
+    let top_levels: Vec<_> = top_levels
+        .iter()
+        .map(|i| {
+            TopLevelStats::new(&instantiate_circuit(
+                i,
+                &mut UniqueVariableRefGenerator::new(),
+                vec![],
+            ))
+        })
+        .collect();
+
+    // every circuit in the hierarchy of a top level, in the order they are written in
+    let mut used = HashSet::new();
+    let mut stack: Vec<_> = top_levels.iter().map(TopLevelStats::hierarchy).collect();
+    while let Some(i) = stack.pop() {
+        used.insert(i.circuit.clone());
+        stack.extend(&i.children);
+    }
+
+    Ok(DesignStats {
+        circuits: circuits
+            .into_iter()
+            .filter(|i| top.is_none() || used.contains(&i.name))
+            .collect(),
+        top_levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::design_stats;
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+
+    #[test]
+    fn adders() {
+        let src = "
+circuit add: a b c_in -> o c_out {
+    o = a xor b xor c_in;
+    c_out = (a and b) or ((a xor b) and c_in);
+}
+
+circuit add2: a0 a1 b0 b1 c_in -> o0 o1 c_out {
+    o0, c0 = add(a0, b0, c_in);
+    o1, c_out = add(a1, b1, c0);
+}
+
+circuit inv: a -> o {
+    o = not(a);
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+        let program =
+            desugar_program(&parser.parse_program().nice_unwrap_panic()).nice_unwrap_panic();
+
+        let stats = design_stats(&program, None).nice_unwrap_panic();
+        let add = &stats.circuits()[0];
+        assert_eq!(add.gates().get("xor"), 3);
+        assert_eq!(add.gates().total(), 6);
+        assert_eq!(stats.circuits()[1].instances(), ["add", "add"]);
+
+        // add isn't a top level, it is used by add2
+        let tops: Vec<_> = stats.top_levels().iter().map(|i| i.circuit()).collect();
+        assert_eq!(tops, vec!["add2", "inv"]);
+
+        let add2 = &stats.top_levels()[0];
+        assert_eq!(add2.gates().total(), 12);
+        assert_eq!(add2.instances(), 2);
+        assert_eq!(add2.max_fan_out(), 3);
+        // the carry between the adders is one net, named after where it is driven
+        assert!(add2.fan_out().contains(&("add2.add.c_out".to_string(), 2)));
+        assert!(add2.fan_out().iter().all(|(i, _)| i != "add2.c0"));
+
+        let mut json = Vec::new();
+        stats.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(r#"{"circuits":[{"name":"add","gates":{"not":0,"and":2,"or":1,"#));
+        assert!(json.contains(r#""hierarchy":{"circuit":"add2","instances":[{"circuit":"add","instances":[]},{"circuit":"add","instances":[]}]}"#));
+
+        let stats = design_stats(&program, Some("add2")).nice_unwrap_panic();
+        let circuits: Vec<_> = stats.circuits().iter().map(|i| i.name()).collect();
+        assert_eq!(circuits, vec!["add", "add2"]);
+        assert_eq!(stats.top_levels().len(), 1);
+    }
+}
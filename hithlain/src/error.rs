@@ -104,3 +104,33 @@ pub enum HithlainError {
     #[diagnostic(transparent)]
     SourceError(#[from] SourceError),
 }
+
+/// The candidate closest to `name`, when it's close enough to probably be a typo.
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    fn distance(a: &str, b: &str) -> usize {
+        let b: Vec<_> = b.chars().collect();
+        let mut previous: Vec<_> = (0..=b.len()).collect();
+
+        for (i, ca) in a.chars().enumerate() {
+            let mut current = vec![i + 1];
+            for (j, cb) in b.iter().enumerate() {
+                let substitution = previous[j] + usize::from(ca != *cb);
+                current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+            }
+            previous = current;
+        }
+
+        previous[b.len()]
+    }
+
+    let max = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|i| (distance(name, i), i))
+        .filter(|(d, _)| *d <= max)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, i)| i)
+}
@@ -11,7 +11,26 @@ fn generate_sentence(g: &Grammar) -> String {
         let res = g.generate_callback(|ident, value| match ident {
             "number" => value.len() < 10,
             "name" => ![
-                "test", "circuit", "at", "every", "or", "and", "nor", "nand", "xor", "xnor", "not",
+                "test",
+                "circuit",
+                "at",
+                "every",
+                "or",
+                "and",
+                "nor",
+                "nand",
+                "xor",
+                "xnor",
+                "not",
+                "check",
+                "for",
+                "always",
+                "stable",
+                "eventually",
+                "from",
+                "to",
+                "during",
+                "within",
             ]
             .contains(&value),
             _ => true,
@@ -0,0 +1,41 @@
+//! Generated programs, shared by the tests and benchmarks. Not part of the library,
+//! the benchmarks include this file themselves.
+
+use std::fmt::Write;
+
+/// A ripple carry adder of full adders with `bits` bits, named `ripple`, and the names of its inputs.
+#[must_use]
+pub fn ripple_carry_adder(bits: usize) -> (String, Vec<String>) {
+    let mut src = String::from(
+        "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or ((a xor b) and c_in);
+        }
+        ",
+    );
+
+    let inputs: Vec<_> = (0..bits)
+        .flat_map(|i| [format!("a{}", i), format!("b{}", i)])
+        .collect();
+    let outputs: Vec<_> = (0..bits).map(|i| format!("o{}", i)).collect();
+
+    writeln!(
+        src,
+        "circuit ripple: {} -> {} c {{",
+        inputs.join(" "),
+        outputs.join(" ")
+    )
+    .unwrap();
+    for i in 0..bits {
+        let carry = if i == 0 {
+            "0".to_string()
+        } else {
+            format!("c{}", i)
+        };
+        writeln!(src, "o{i}, c{} = add(a{i}, b{i}, {});", i + 1, carry, i = i).unwrap();
+    }
+    writeln!(src, "c = c{};\n}}", bits).unwrap();
+
+    (src, inputs)
+}
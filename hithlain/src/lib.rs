@@ -3,6 +3,7 @@
 #![allow(clippy::implicit_hasher)]
 #![allow(clippy::missing_errors_doc)]
 
+pub mod analyze;
 pub mod error;
 pub mod parse;
 pub mod sim;
@@ -13,6 +14,8 @@ pub use miette;
 
 #[cfg(test)]
 pub mod fuzz;
+#[cfg(test)]
+mod generate;
 
 // TODO: vhdl translation
 // mod vhdl;
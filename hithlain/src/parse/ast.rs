@@ -2,7 +2,7 @@ use crate::parse::span::Span;
 use crate::sim::instantiated_ast::{LocalizedVariable, Package};
 use crate::time::{Duration, Instant};
 use derivative::Derivative;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, Derivative, Clone)]
 #[derivative(PartialEq, Hash)]
@@ -15,7 +15,7 @@ pub struct Variable(
 
 impl Variable {
     #[must_use]
-    pub fn localize(&self, path: Rc<Vec<Package>>) -> LocalizedVariable {
+    pub fn localize(&self, path: Arc<Vec<Package>>) -> LocalizedVariable {
         LocalizedVariable {
             variable: self.clone(),
             path,
@@ -31,9 +31,18 @@ pub enum Constant {
     Number(u64),
 }
 
+/// A reference to a signal inside an instantiated circuit, like `cpu.alu.carry`.
+/// Instances are named after their circuit.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct HierarchicalPath {
+    pub(crate) instances: Vec<Variable>,
+    pub(crate) variable: Variable,
+}
+
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub enum Atom {
     Variable(Variable),
+    Hierarchical(HierarchicalPath),
     Constant(Constant),
     Expr(Box<Expr>),
 }
@@ -67,10 +76,12 @@ pub enum Expr {
         a: Box<Expr>,
         b: Box<Expr>,
         action: BinaryAction,
+        span: Span,
     },
     NaryOp {
         params: Vec<Expr>,
         action: NaryAction,
+        span: Span,
     },
     Atom(Atom),
 }
@@ -79,12 +90,61 @@ pub enum Expr {
 pub struct Assignment {
     pub(crate) into: Vec<Variable>,
     pub(crate) expr: Expr,
+    /// the outcome is assigned this long after the inputs change
+    pub(crate) delay: Option<Duration>,
+}
+
+/// When the expression of a temporal assertion is checked.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum Temporal {
+    /// true on every change between two absolute times
+    Always { from: Instant, to: Instant },
+    /// doesn't change for a duration after the assertion
+    Stable(Duration),
+    /// becomes true at some point within a duration after the assertion
+    Eventually(Duration),
+}
+
+/// A condition of which the simulation counts how often it becomes true.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct CoverBin {
+    /// the name it was given, or the text of the condition
+    pub(crate) name: String,
+    pub(crate) span: Span,
+}
+
+impl CoverBin {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub enum Statement {
     Assignment(Assignment),
-    Assert { expr: Expr, span: Span },
+    Assert {
+        expr: Expr,
+        span: Span,
+    },
+    TemporalAssert {
+        expr: Expr,
+        span: Span,
+        temporal: Temporal,
+    },
+    Cover {
+        expr: Expr,
+        bin: CoverBin,
+    },
+    Force {
+        path: HierarchicalPath,
+        value: Constant,
+    },
 }
 
 #[derive(Debug, Eq, PartialEq, Hash)]
@@ -107,6 +167,9 @@ pub struct Circuit {
     pub(crate) outputs: Vec<Variable>,
 
     pub(crate) body: Vec<Statement>,
+
+    /// the delay of every builtin gate in statements without a delay of their own
+    pub(crate) delay: Option<Duration>,
 }
 
 pub struct Process {
@@ -118,13 +181,42 @@ pub struct Process {
     pub(crate) body: Vec<StatementOrTime>,
 }
 
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum Attribute {
+    ShouldFail,
+    Ignore,
+    Timeout(Duration),
+    Tag(String),
+    Delay(Duration),
+}
+
+impl Attribute {
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Attribute::ShouldFail => "should_fail",
+            Attribute::Ignore => "ignore",
+            Attribute::Timeout(_) => "timeout",
+            Attribute::Tag(_) => "tag",
+            Attribute::Delay(_) => "delay",
+        }
+    }
+}
+
 pub struct Test {
     pub(crate) name: Variable,
+    pub(crate) attributes: Vec<Attribute>,
     pub(crate) body: Vec<StatementOrTime>,
 }
 
+pub struct Check {
+    pub(crate) circuit: Variable,
+    pub(crate) body: Vec<Statement>,
+}
+
 pub struct Program {
     pub(crate) circuits: Vec<Circuit>,
     pub(crate) processes: Vec<Process>,
     pub(crate) tests: Vec<Test>,
+    pub(crate) checks: Vec<Check>,
 }
@@ -1,16 +1,18 @@
 use crate::error::Warn;
 use crate::parse::ast as a;
 use crate::parse::ast::{
-    Atom, BinaryAction, Expr, NaryAction, StatementOrTime, UnaryAction, Variable,
+    Atom, BinaryAction, Expr, HierarchicalPath, NaryAction, StatementOrTime, UnaryAction, Variable,
 };
 use crate::parse::desugared_ast as d;
-use crate::parse::desugared_ast::{BinaryBuiltin, Program, Statement, TimedBlock};
+use crate::parse::desugared_ast::{BinaryBuiltin, Program, Statement, TestAttributes, TimedBlock};
+use crate::parse::loops::{find_combinational_loop, output_dependencies, CombinationalLoop};
 use crate::parse::scope::{DuplicateDefinition, Scope, VariableRef, VariableType};
-use crate::time::Instant;
+use crate::parse::span::Span;
+use crate::time::{Duration, Instant};
 use miette::{Diagnostic, NamedSource, SourceSpan};
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug, Diagnostic)]
@@ -34,6 +36,47 @@ pub enum DesugarError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     CircuitDoesntExist(#[from] CircuitDoesntExist),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    InvalidHierarchicalReference(#[from] InvalidHierarchicalReference),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    CombinationalLoop(#[from] CombinationalLoop),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    MultipleDrivers(#[from] MultipleDrivers),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnconnectedInput(#[from] UnconnectedInput),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    TooManyCheckInputs(#[from] TooManyCheckInputs),
+}
+
+/// Checks try every combination of inputs, which doubles with every input.
+pub const MAX_CHECK_INPUTS: usize = 24;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("circuit {} has too many inputs to check every combination of them", circuit.0)]
+#[diagnostic(help(
+    "it has {} inputs, at most {} are supported. test it with vectors instead",
+    inputs,
+    MAX_CHECK_INPUTS
+))]
+pub struct TooManyCheckInputs {
+    #[source_code]
+    src: NamedSource,
+
+    circuit: Variable,
+    inputs: usize,
+
+    #[label("checked here")]
+    span: SourceSpan,
 }
 
 #[derive(Error, Debug, Diagnostic)]
@@ -83,6 +126,67 @@ pub struct CircuitDoesntExist {
     span: SourceSpan,
 }
 
+#[derive(Error, Debug, Diagnostic)]
+#[error("{}", reason)]
+#[diagnostic()]
+pub struct InvalidHierarchicalReference {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("here")]
+    span: SourceSpan,
+
+    reason: String,
+}
+
+impl InvalidHierarchicalReference {
+    fn new(path: &HierarchicalPath, reason: String) -> Self {
+        let spans: Vec<_> = path
+            .instances
+            .iter()
+            .chain(Some(&path.variable))
+            .filter_map(|i| i.1.clone())
+            .collect();
+        let span = Span::merge(&spans);
+
+        Self {
+            src: span.source().clone().into(),
+            span: span.into(),
+            reason,
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("signal {} is driven more than once", variable.0)]
+#[diagnostic(help("every signal in a circuit can only be assigned once. inputs are driven where the circuit is used"))]
+pub struct MultipleDrivers {
+    #[source_code]
+    src: NamedSource,
+
+    variable: Variable,
+
+    #[label("first driver")]
+    prev: SourceSpan,
+
+    #[label("second driver")]
+    def: SourceSpan,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("not every input of circuit {} is connected", circuit)]
+#[diagnostic(help("nothing drives {}", unconnected))]
+pub struct UnconnectedInput {
+    #[source_code]
+    src: NamedSource,
+
+    circuit: String,
+    unconnected: String,
+
+    #[label("used here")]
+    span: SourceSpan,
+}
+
 #[derive(Error, Debug, Diagnostic)]
 #[error("output variable unassigned in circuit {}", variable.0)]
 #[diagnostic(severity = "warning")]
@@ -140,19 +244,29 @@ pub fn desugar_program(p: &a::Program) -> Result<d::Program, DesugarError> {
 
     let mut tests = Vec::new();
     for i in &p.tests {
-        tests.push(Rc::new(desugar_test(i, &mut circuits)?));
+        tests.push(Arc::new(desugar_test(i, &mut circuits)?));
+    }
+
+    let mut checks = Vec::new();
+    for i in &p.checks {
+        checks.push(Arc::new(desugar_check(i, &mut circuits)?));
     }
 
     Ok(Program {
         circuits: desugared_circuits,
         tests,
+        checks,
     })
 }
 
 fn desugar_circuit(
     circuit: &a::Circuit,
-    circuit_names: &mut HashMap<&a::Variable, Option<Rc<d::Circuit>>>,
-) -> Result<Result<Rc<d::Circuit>, DesugarError>, Vec<Variable>> {
+    circuit_names: &mut HashMap<&a::Variable, Option<Arc<d::Circuit>>>,
+) -> Result<Result<Arc<d::Circuit>, DesugarError>, Vec<Variable>> {
+    if let Err(e) = check_drivers(circuit) {
+        return Ok(Err(e.into()));
+    }
+
     let mut scope = Scope::new();
 
     let mut inputs = Vec::new();
@@ -173,13 +287,23 @@ fn desugar_circuit(
 
     let mut body = Vec::new();
     for i in &circuit.body {
-        match desugar_statement(i, circuit_names, &mut scope) {
+        match desugar_statement(i, circuit_names, &mut scope, circuit.delay) {
             Ok(Ok(i)) => body.extend(i),
             Err(needed) => return Err(needed),
             Ok(Err(e)) => return Ok(Err(e)),
         }
     }
 
+    for i in &body {
+        if let Statement::MoveHierarchical(_, path) | Statement::Force(path, _) = i {
+            return Ok(Err(InvalidHierarchicalReference::new(
+                path,
+                "hierarchical references can only be used in tests and checks".to_string(),
+            )
+            .into()));
+        }
+    }
+
     for i in scope.variables.values() {
         if i.0.variable_type == VariableType::Out && !i.0.written.load(Ordering::SeqCst) {
             if let Some(ref span) = i.0.variable.1 {
@@ -224,17 +348,52 @@ fn desugar_circuit(
         }
     }
 
-    Ok(Ok(Rc::new(d::Circuit {
+    if let Err(e) = find_combinational_loop(&body) {
+        return Ok(Err(e.into()));
+    }
+    let output_dependencies = output_dependencies(&inputs, &outputs, &body);
+
+    Ok(Ok(Arc::new(d::Circuit {
         name: circuit.name.clone(),
         inputs,
         outputs,
         body,
+        output_dependencies,
     })))
 }
 
+/// Makes sure no signal in a circuit is assigned by more than one statement. The inputs
+/// count as assigned already, by whatever instantiates the circuit.
+fn check_drivers(circuit: &a::Circuit) -> Result<(), MultipleDrivers> {
+    let mut drivers: HashMap<_, _> = circuit.inputs.iter().map(|i| (i, i)).collect();
+
+    for i in &circuit.body {
+        let into = match i {
+            a::Statement::Assignment(a) => &a.into,
+            _ => continue,
+        };
+
+        for into in into {
+            if let Some(prev) = drivers.insert(into, into) {
+                let span_1 = into.1.clone().expect("must have span");
+                let span_2 = prev.1.clone().expect("must have span");
+
+                return Err(MultipleDrivers {
+                    src: span_1.source().clone().into(),
+                    variable: into.clone(),
+                    prev: span_2.into(),
+                    def: span_1.into(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn desugar_test(
     test: &a::Test,
-    circuit_names: &mut HashMap<&a::Variable, Option<Rc<d::Circuit>>>,
+    circuit_names: &mut HashMap<&a::Variable, Option<Arc<d::Circuit>>>,
 ) -> Result<d::Process, DesugarError> {
     let mut scope = Scope::new();
 
@@ -269,19 +428,202 @@ fn desugar_test(
         }
     }
 
+    let statements: Vec<_> = timed_blocks.iter().flat_map(|i| &i.block).collect();
+    check_hierarchical_references(&statements, &test.name)?;
+    find_combinational_loop(statements.iter().copied())?;
+
+    let mut attributes = TestAttributes::default();
+    for i in &test.attributes {
+        match i {
+            a::Attribute::ShouldFail => attributes.should_fail = true,
+            a::Attribute::Ignore => attributes.ignore = true,
+            a::Attribute::Timeout(d) => attributes.timeout = Some(*d),
+            a::Attribute::Tag(t) => attributes.tags.push(t.clone()),
+            // the parser only allows delays on circuits
+            a::Attribute::Delay(_) => unreachable!("delay attribute on a test"),
+        }
+    }
+
     Ok(d::Process {
         name: test.name.clone(),
         timed_blocks,
+        attributes,
 
         inputs: vec![],
         outputs: vec![],
     })
 }
 
+fn desugar_check(
+    check: &a::Check,
+    circuit_names: &mut HashMap<&a::Variable, Option<Arc<d::Circuit>>>,
+) -> Result<d::Check, DesugarError> {
+    let circuit = if let Some(Some(circuit)) = circuit_names.get(&check.circuit) {
+        circuit.clone()
+    } else if let Some(ref i) = check.circuit.1 {
+        return Err(CircuitDoesntExist {
+            src: i.source().clone().into(),
+            variable: check.circuit.clone(),
+            span: i.clone().into(),
+        }
+        .into());
+    } else {
+        unreachable!("circuit name must have a source location");
+    };
+
+    if circuit.inputs.len() > MAX_CHECK_INPUTS {
+        let span = check
+            .circuit
+            .1
+            .clone()
+            .expect("circuit name must have a source location");
+        return Err(TooManyCheckInputs {
+            src: span.source().clone().into(),
+            circuit: check.circuit.clone(),
+            inputs: circuit.inputs.len(),
+            span: span.into(),
+        }
+        .into());
+    }
+
+    // the ports of the circuit are in scope under their own names
+    let mut scope = Scope::new();
+
+    let mut inputs = Vec::new();
+    for i in &circuit.inputs {
+        inputs.push(scope.define_variable(&i.0.variable, VariableType::In)?);
+    }
+
+    let mut outputs = Vec::new();
+    for i in &circuit.outputs {
+        outputs.push(scope.define_variable(&i.0.variable, VariableType::Out)?);
+    }
+
+    let mut body = vec![Statement::Custom {
+        inputs: inputs.clone(),
+        circuit: circuit.clone(),
+        into: outputs.clone(),
+        assigned: vec![None; outputs.len()],
+    }];
+
+    for i in &check.body {
+        match desugar_statement(i, circuit_names, &mut scope, None) {
+            Ok(i) => body.extend(i?),
+            Err(e) => unreachable!(
+                "all circuits have been resolved when checks are desugared: {:?}",
+                e
+            ),
+        }
+    }
+
+    for i in scope.variables.values() {
+        if i.0.variable_type == VariableType::Intermediate && !i.0.written.load(Ordering::SeqCst) {
+            if let Some(ref span) = i.0.variable.1 {
+                return Err(VariableNeverWritten {
+                    src: span.source().clone().into(),
+                    variable: i.0.variable.clone(),
+                    span: span.clone().into(),
+                }
+                .into());
+            }
+
+            unreachable!("out variable must have source reference");
+        }
+    }
+
+    check_hierarchical_references(&body.iter().collect::<Vec<_>>(), &check.circuit)?;
+    find_combinational_loop(&body)?;
+
+    Ok(d::Check {
+        name: check.circuit.clone(),
+        circuit,
+        inputs,
+        outputs,
+        body,
+    })
+}
+
+/// Finds the only instance of a circuit called `name` among `statements`.
+fn find_instance<'a>(
+    statements: &[&'a d::Statement],
+    name: &Variable,
+    parent: &Variable,
+    path: &HierarchicalPath,
+) -> Result<&'a Arc<d::Circuit>, InvalidHierarchicalReference> {
+    let mut instances = statements.iter().filter_map(|i| match i {
+        Statement::Custom { circuit, .. } if &circuit.name == name => Some(circuit),
+        _ => None,
+    });
+
+    match (instances.next(), instances.next()) {
+        (Some(circuit), None) => Ok(circuit),
+        (None, _) => Err(InvalidHierarchicalReference::new(
+            path,
+            format!("no instance of circuit {} in {}", name.0, parent.0),
+        )),
+        (Some(_), Some(_)) => Err(InvalidHierarchicalReference::new(
+            path,
+            format!(
+                "circuit {} is instantiated more than once in {}, so {}.{} is ambiguous",
+                name.0, parent.0, parent.0, name.0
+            ),
+        )),
+    }
+}
+
+/// Makes sure every hierarchical reference in a test refers to exactly one signal.
+fn check_hierarchical_references(
+    statements: &[&d::Statement],
+    owner: &Variable,
+) -> Result<(), InvalidHierarchicalReference> {
+    for i in statements {
+        let path = match i {
+            Statement::MoveHierarchical(_, path) | Statement::Force(path, _) => path,
+            _ => continue,
+        };
+
+        let mut parent = owner;
+        let mut candidates = statements.to_vec();
+        let mut circuit = None;
+
+        for name in &path.instances {
+            let c = find_instance(&candidates, name, parent, path)?;
+            candidates = c.body.iter().collect();
+            parent = &c.name;
+            circuit = Some(c);
+        }
+
+        let circuit = circuit.expect("hierarchical paths contain at least one instance");
+        let exists = circuit
+            .inputs
+            .iter()
+            .chain(&circuit.outputs)
+            .chain(
+                circuit
+                    .body
+                    .iter()
+                    .flat_map(|s| s.reads().into_iter().chain(s.writes())),
+            )
+            .any(|v| v.0.variable_type != VariableType::Temp && v.0.variable == path.variable);
+
+        if !exists {
+            return Err(InvalidHierarchicalReference::new(
+                path,
+                format!(
+                    "circuit {} has no signal {}",
+                    circuit.name.0, path.variable.0
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 fn desugar_timed_blocks(
     statements: &[a::StatementOrTime],
     blocks: &mut Vec<d::TimedBlock>,
-    circuit_names: &mut HashMap<&Variable, Option<Rc<d::Circuit>>>,
+    circuit_names: &mut HashMap<&Variable, Option<Arc<d::Circuit>>>,
     scope: &mut Scope,
 ) -> Result<(), DesugarError> {
     let mut statement_iter = statements.iter();
@@ -309,7 +651,7 @@ fn desugar_timed_blocks(
                 }
             }
             Some(StatementOrTime::Statement(s)) => {
-                let ds = match desugar_statement(s, circuit_names, scope) {
+                let ds = match desugar_statement(s, circuit_names, scope, None) {
                     Ok(i) => i,
                     Err(e) => {
                         dbg!(e);
@@ -329,10 +671,26 @@ fn desugar_timed_blocks(
     Ok(())
 }
 
+/// Plain moves and instance calls don't compute anything themselves,
+/// so they are covered where their outcome is assigned instead.
+fn set_assigned(statement: &mut Statement, into: &[Variable]) {
+    match statement {
+        Statement::Move(_, _, span) => *span = into.first().and_then(|i| i.1.clone()),
+        Statement::Custom { assigned, .. } => {
+            for (span, variable) in assigned.iter_mut().zip(into) {
+                *span = variable.1.clone();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `default_delay` is the delay of builtin gates in assignments without a delay of their own.
 fn desugar_statement(
     statement: &a::Statement,
-    circuit_names: &mut HashMap<&a::Variable, Option<Rc<d::Circuit>>>,
+    circuit_names: &mut HashMap<&a::Variable, Option<Arc<d::Circuit>>>,
     scope: &mut Scope,
+    default_delay: Option<Duration>,
 ) -> Result<Result<Vec<d::Statement>, DesugarError>, Vec<Variable>> {
     let mut res = Vec::new();
 
@@ -348,13 +706,76 @@ fn desugar_statement(
                 res_vars.push(var);
             }
 
-            match desugar_expr(&a.expr, res_vars, &mut res, circuit_names, scope) {
-                Ok(Ok(_)) => Ok(Ok(res)),
-                Err(needed) => Err(needed),
-                Ok(Err(e)) => Ok(Err(e)),
+            // the expression is computed without delay into temporaries,
+            // which are then moved into the assigned variables after the delay
+            let targets = if a.delay.is_some() {
+                let mut temps = Vec::new();
+                for _ in &res_vars {
+                    temps.push(match scope.define_temp_variable() {
+                        Ok(i) => i,
+                        Err(e) => return Ok(Err(e.into())),
+                    });
+                }
+                temps
+            } else {
+                res_vars.clone()
+            };
+
+            match desugar_expr(&a.expr, targets.clone(), &mut res, circuit_names, scope) {
+                Ok(Ok(_)) => (),
+                Err(needed) => return Err(needed),
+                Ok(Err(e)) => return Ok(Err(e)),
+            }
+
+            // the outermost part of the expression is desugared last
+            if let Some(last) = res.last_mut() {
+                set_assigned(last, &a.into);
+            }
+
+            if let Some(delay) = a.delay {
+                for (into, temp) in res_vars.into_iter().zip(targets) {
+                    res.push(Statement::Delayed(
+                        Box::new(Statement::Move(into, temp, None)),
+                        delay,
+                    ));
+                }
+            } else if let Some(delay) = default_delay {
+                res = res
+                    .into_iter()
+                    .map(|i| {
+                        if i.is_builtin() {
+                            Statement::Delayed(Box::new(i), delay)
+                        } else {
+                            i
+                        }
+                    })
+                    .collect();
+            }
+
+            Ok(Ok(res))
+        }
+        a::Statement::Force { path, value } => {
+            res.push(Statement::Force(path.clone(), value.clone()));
+
+            Ok(Ok(res))
+        }
+        a::Statement::Cover { expr, bin } => {
+            let c_var = match scope.define_temp_variable() {
+                Ok(i) => i,
+                Err(e) => return Ok(Err(e.into())),
+            };
+
+            match desugar_expr(expr, vec![c_var.clone()], &mut res, circuit_names, scope) {
+                Ok(Ok(_)) => (),
+                Err(needed) => return Err(needed),
+                Ok(Err(e)) => return Ok(Err(e)),
             }
+
+            res.push(Statement::Cover(c_var, bin.clone()));
+
+            Ok(Ok(res))
         }
-        a::Statement::Assert { expr, span } => {
+        a::Statement::Assert { expr, span } | a::Statement::TemporalAssert { expr, span, .. } => {
             let a_var = match scope.define_temp_variable() {
                 Ok(i) => i,
                 Err(e) => return Ok(Err(e.into())),
@@ -366,7 +787,15 @@ fn desugar_statement(
                 Ok(Err(e)) => return Ok(Err(e)),
             }
 
-            res.push(Statement::Assert(a_var, span.clone()));
+            if let a::Statement::TemporalAssert { temporal, .. } = statement {
+                res.push(Statement::TemporalAssert(
+                    a_var,
+                    span.clone(),
+                    temporal.clone(),
+                ));
+            } else {
+                res.push(Statement::Assert(a_var, span.clone()));
+            }
 
             Ok(Ok(res))
         }
@@ -378,7 +807,7 @@ fn desugar_expr(
     expr: &a::Expr,
     into: Vec<VariableRef>,
     res: &mut Vec<d::Statement>,
-    circuit_names: &mut HashMap<&a::Variable, Option<Rc<d::Circuit>>>,
+    circuit_names: &mut HashMap<&a::Variable, Option<Arc<d::Circuit>>>,
     scope: &mut Scope,
 ) -> Result<Result<(), DesugarError>, Vec<Variable>> {
     macro_rules! cleanup {
@@ -416,7 +845,7 @@ fn desugar_expr(
     }
 
     match expr {
-        Expr::BinaryOp { a, b, action } => {
+        Expr::BinaryOp { a, b, action, span } => {
             let a_var = match scope.define_temp_variable() {
                 Ok(i) => i,
                 Err(e) => return Ok(Err(e.into())),
@@ -447,6 +876,7 @@ fn desugar_expr(
                         a: a_var,
                         b: b_var,
                         into: get_first!(into),
+                        span: span.clone(),
                     }));
                 }
                 BinaryAction::Or => {
@@ -454,6 +884,7 @@ fn desugar_expr(
                         a: a_var,
                         b: b_var,
                         into: get_first!(into),
+                        span: span.clone(),
                     }));
                 }
                 BinaryAction::Nand => {
@@ -461,6 +892,7 @@ fn desugar_expr(
                         a: a_var,
                         b: b_var,
                         into: get_first!(into),
+                        span: span.clone(),
                     }));
                 }
                 BinaryAction::Nor => {
@@ -468,6 +900,7 @@ fn desugar_expr(
                         a: a_var,
                         b: b_var,
                         into: get_first!(into),
+                        span: span.clone(),
                     }));
                 }
                 BinaryAction::Xor => {
@@ -475,6 +908,7 @@ fn desugar_expr(
                         a: a_var,
                         b: b_var,
                         into: get_first!(into),
+                        span: span.clone(),
                     }));
                 }
                 BinaryAction::Xnor => {
@@ -482,12 +916,17 @@ fn desugar_expr(
                         a: a_var,
                         b: b_var,
                         into: get_first!(into),
+                        span: span.clone(),
                     }));
                 }
                 BinaryAction::Custom(_) => unimplemented!("TODO"), // TODO
             }
         }
-        Expr::NaryOp { params, action } => {
+        Expr::NaryOp {
+            params,
+            action,
+            span,
+        } => {
             let mut param_vars = Vec::new();
             for i in params {
                 let var = match scope.define_temp_variable() {
@@ -512,6 +951,7 @@ fn desugar_expr(
                         res.push(Statement::Not {
                             input: get_first!(param_vars),
                             into: get_first!(into),
+                            span: span.clone(),
                         });
                     }
                 },
@@ -519,9 +959,28 @@ fn desugar_expr(
                 NaryAction::Custom(c) => {
                     if let Some(circuit_exists) = circuit_names.get(c) {
                         if let Some(circuit) = circuit_exists {
+                            if param_vars.len() < circuit.inputs.len() {
+                                let span =
+                                    c.1.clone()
+                                        .expect("circuit name must have a source location");
+                                let unconnected: Vec<_> = circuit.inputs[param_vars.len()..]
+                                    .iter()
+                                    .map(|i| i.0.variable.0.as_str())
+                                    .collect();
+
+                                return Ok(Err(UnconnectedInput {
+                                    src: span.source().clone().into(),
+                                    circuit: c.0.clone(),
+                                    unconnected: unconnected.join(", "),
+                                    span: span.into(),
+                                }
+                                .into()));
+                            }
+
                             res.push(Statement::Custom {
                                 inputs: param_vars,
                                 circuit: circuit.clone(),
+                                assigned: vec![None; into.len()],
                                 into,
                             });
                         } else {
@@ -548,8 +1007,12 @@ fn desugar_expr(
                         Ok(i) => i,
                         Err(e) => return Ok(Err(e.into())),
                     },
+                    None,
                 ));
             }
+            Atom::Hierarchical(path) => {
+                res.push(Statement::MoveHierarchical(get_first!(into), path.clone()));
+            }
             Atom::Constant(v) => {
                 res.push(Statement::Set(get_first!(into), v.clone()));
             }
@@ -565,10 +1028,11 @@ fn desugar_expr(
 #[cfg(test)]
 mod tests {
     use crate::error::NiceUnwrap;
-    use crate::parse::desugar::desugar_program;
+    use crate::parse::desugar::{desugar_program, DesugarError, MAX_CHECK_INPUTS};
     use crate::parse::lexer::lex;
     use crate::parse::parser::Parser;
     use crate::parse::source::Source;
+    use miette::Diagnostic;
 
     #[test]
     fn test_smoke() {
@@ -602,4 +1066,185 @@ mod tests {
 
         desugar_program(&parsed).nice_unwrap_panic();
     }
+
+    #[test]
+    fn too_many_check_inputs() {
+        let inputs: Vec<_> = (0..=MAX_CHECK_INPUTS).map(|i| format!("i{}", i)).collect();
+        let src = format!(
+            "
+        circuit wide: {} -> o {{
+            o = i0 and i1;
+        }}
+
+        check wide for all inputs {{
+            assert o == i0 and i1;
+        }}
+        ",
+            inputs.join(" ")
+        );
+
+        let lexed = lex(&Source::test(&src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        assert!(matches!(
+            desugar_program(&parsed),
+            Err(DesugarError::TooManyCheckInputs(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_hierarchical_reference() {
+        let src = "
+        circuit something: a b -> d {
+            d = a and b;
+        }
+
+        test main {
+            x = something(a, b);
+
+            at 0ns:
+                a = 1;
+                b = 1;
+
+                assert something.y == 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        assert!(matches!(
+            desugar_program(&parsed),
+            Err(DesugarError::InvalidHierarchicalReference(_))
+        ));
+    }
+
+    #[test]
+    fn combinational_loop() {
+        let src = "
+        circuit something: a -> x {
+            y = a and x;
+            x = not(y);
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        match desugar_program(&parsed) {
+            Err(DesugarError::CombinationalLoop(e)) => {
+                assert_eq!(e.to_string(), "combinational loop: y -> x -> y");
+            }
+            _ => panic!("expected a combinational loop"),
+        }
+    }
+
+    #[test]
+    fn loop_through_instance() {
+        // `b` only depends on `y`, so feeding `b` back into `x` is fine
+        let src = "
+        circuit pass: x y -> a b {
+            a = x;
+            b = y;
+        }
+
+        circuit fine: i -> o {
+            o, c = pass(c, i);
+        }
+
+        circuit broken: i -> o {
+            c, o = pass(c, i);
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        match desugar_program(&parsed) {
+            Err(DesugarError::CombinationalLoop(e)) => {
+                assert_eq!(e.to_string(), "combinational loop: c -> c");
+            }
+            _ => panic!("expected a combinational loop"),
+        }
+    }
+
+    #[test]
+    fn multiple_drivers() {
+        let src = "
+        circuit something: a b -> x {
+            x = a and b;
+            x, y = pass(a, b);
+        }
+
+        circuit pass: a b -> x y {
+            x = a;
+            y = b;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        match desugar_program(&parsed) {
+            Err(DesugarError::MultipleDrivers(e)) => {
+                assert_eq!(e.to_string(), "signal x is driven more than once");
+            }
+            _ => panic!("expected multiple drivers"),
+        }
+    }
+
+    #[test]
+    fn driven_input() {
+        let src = "
+        circuit something: a b -> x {
+            x = a and b;
+            a = 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        assert!(matches!(
+            desugar_program(&parsed),
+            Err(DesugarError::MultipleDrivers(_))
+        ));
+    }
+
+    #[test]
+    fn unconnected_input() {
+        let src = "
+        circuit something: a b c -> x {
+            x = a and (b or c);
+        }
+
+        test main {
+            x = something(1);
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        match desugar_program(&parsed) {
+            Err(DesugarError::UnconnectedInput(e)) => {
+                assert_eq!(e.help().unwrap().to_string(), "nothing drives b, c");
+            }
+            _ => panic!("expected an unconnected input"),
+        }
+    }
 }
@@ -1,22 +1,46 @@
-use crate::parse::ast::{Constant, Variable};
+use crate::parse::ast::{Constant, CoverBin, HierarchicalPath, Temporal, Variable};
 use crate::parse::scope::VariableRef;
 use crate::parse::span::Span;
-use crate::time::Instant;
-use std::rc::Rc;
+use crate::time::{Duration, Instant};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct BinaryBuiltin {
     pub(crate) a: VariableRef,
     pub(crate) b: VariableRef,
     pub(crate) into: VariableRef,
+    /// the expression in the source
+    pub(crate) span: Span,
+}
+
+/// Where in the source a variable is computed, for coverage and glitch detection.
+#[derive(Debug, Clone)]
+pub enum Driver {
+    /// a builtin gate expression
+    Gate(Span),
+    /// a plain assignment or instance call, at the variable it assigns
+    Assignment(Span),
+}
+
+impl Driver {
+    #[must_use]
+    pub fn span(&self) -> &Span {
+        match self {
+            Driver::Gate(span) | Driver::Assignment(span) => span,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub enum Statement {
     Assert(VariableRef, Span),
+    TemporalAssert(VariableRef, Span, Temporal),
+    /// counts how often the variable becomes 1
+    Cover(VariableRef, CoverBin),
     Not {
         input: VariableRef,
         into: VariableRef,
+        span: Span,
     },
     And(BinaryBuiltin),
     Or(BinaryBuiltin),
@@ -26,11 +50,107 @@ pub enum Statement {
     Xnor(BinaryBuiltin),
     Custom {
         inputs: Vec<VariableRef>,
-        circuit: Rc<Circuit>,
+        circuit: Arc<Circuit>,
         into: Vec<VariableRef>,
+        /// where every output is assigned in the source, if it is
+        assigned: Vec<Option<Span>>,
     },
-    Move(VariableRef, VariableRef),
+    /// the span is where the variable is assigned in the source, if it is
+    Move(VariableRef, VariableRef, Option<Span>),
     Set(VariableRef, Constant),
+    /// move from a signal inside an instance
+    MoveHierarchical(VariableRef, HierarchicalPath),
+    /// force a signal inside an instance to a value, regardless of what drives it
+    Force(HierarchicalPath, Constant),
+    /// a builtin or move that writes its outcome some time after its inputs change
+    Delayed(Box<Statement>, Duration),
+}
+
+impl Statement {
+    /// Whether this is one of the builtin gates.
+    #[must_use]
+    pub fn is_builtin(&self) -> bool {
+        matches!(
+            self,
+            Statement::Not { .. }
+                | Statement::And(_)
+                | Statement::Or(_)
+                | Statement::Nand(_)
+                | Statement::Nor(_)
+                | Statement::Xor(_)
+                | Statement::Xnor(_)
+        )
+    }
+
+    /// The variables this statement reads.
+    #[must_use]
+    pub fn reads(&self) -> Vec<&VariableRef> {
+        match self {
+            Statement::Assert(v, _)
+            | Statement::TemporalAssert(v, _, _)
+            | Statement::Cover(v, _) => vec![v],
+            Statement::Not { input, .. } => vec![input],
+            Statement::And(b)
+            | Statement::Or(b)
+            | Statement::Nand(b)
+            | Statement::Nor(b)
+            | Statement::Xor(b)
+            | Statement::Xnor(b) => vec![&b.a, &b.b],
+            Statement::Custom { inputs, .. } => inputs.iter().collect(),
+            Statement::Move(_, b, _) => vec![b],
+            Statement::Set(_, _) | Statement::MoveHierarchical(_, _) | Statement::Force(_, _) => {
+                vec![]
+            }
+            Statement::Delayed(s, _) => s.reads(),
+        }
+    }
+
+    /// The variables this statement writes.
+    #[must_use]
+    pub fn writes(&self) -> Vec<&VariableRef> {
+        match self {
+            Statement::Assert(_, _)
+            | Statement::TemporalAssert(_, _, _)
+            | Statement::Cover(_, _)
+            | Statement::Force(_, _) => {
+                vec![]
+            }
+            Statement::Not { into, .. } => vec![into],
+            Statement::And(b)
+            | Statement::Or(b)
+            | Statement::Nand(b)
+            | Statement::Nor(b)
+            | Statement::Xor(b)
+            | Statement::Xnor(b) => vec![&b.into],
+            Statement::Custom { into, .. } => into.iter().collect(),
+            Statement::Move(a, _, _) | Statement::Set(a, _) | Statement::MoveHierarchical(a, _) => {
+                vec![a]
+            }
+            Statement::Delayed(s, _) => s.writes(),
+        }
+    }
+
+    /// The variables this statement computes, and where in the source.
+    #[must_use]
+    pub fn drivers(&self) -> Vec<(&VariableRef, Driver)> {
+        match self {
+            Statement::Not { into, span, .. } => vec![(into, Driver::Gate(span.clone()))],
+            Statement::And(b)
+            | Statement::Or(b)
+            | Statement::Nand(b)
+            | Statement::Nor(b)
+            | Statement::Xor(b)
+            | Statement::Xnor(b) => vec![(&b.into, Driver::Gate(b.span.clone()))],
+            Statement::Custom { into, assigned, .. } => into
+                .iter()
+                .zip(assigned)
+                .filter_map(|(v, span)| Some((v, Driver::Assignment(span.clone()?))))
+                .collect(),
+            Statement::Move(a, _, Some(span)) => vec![(a, Driver::Assignment(span.clone()))],
+            Statement::Delayed(s, _) => s.drivers(),
+            _ => vec![],
+        }
+    }
 }
 
 pub struct Circuit {
@@ -40,6 +160,27 @@ pub struct Circuit {
     pub(crate) outputs: Vec<VariableRef>,
 
     pub(crate) body: Vec<Statement>,
+
+    /// for every output, the indices of the inputs it depends on without delay
+    pub(crate) output_dependencies: Vec<Vec<usize>>,
+}
+
+impl Circuit {
+    /// An upper bound on how long the outputs take to settle after an input changes.
+    #[must_use]
+    pub fn max_delay(&self) -> Duration {
+        let nanos = self
+            .body
+            .iter()
+            .map(|i| match i {
+                Statement::Delayed(_, d) => d.nanos(),
+                Statement::Custom { circuit, .. } => circuit.max_delay().nanos(),
+                _ => 0,
+            })
+            .sum();
+
+        Duration::from_nanos(nanos)
+    }
 }
 
 pub struct TimedBlock {
@@ -47,15 +188,38 @@ pub struct TimedBlock {
     pub(crate) block: Vec<Statement>,
 }
 
+#[derive(Default)]
+pub struct TestAttributes {
+    /// the test passes when one of its assertions fails
+    pub(crate) should_fail: bool,
+    /// the test is skipped when running all tests
+    pub(crate) ignore: bool,
+    /// the test fails when it is still running after this much simulated time
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) tags: Vec<String>,
+}
+
 pub struct Process {
     pub(crate) name: Variable,
     pub(crate) timed_blocks: Vec<TimedBlock>,
+    pub(crate) attributes: TestAttributes,
 
     pub(crate) inputs: Vec<VariableRef>,
     pub(crate) outputs: Vec<VariableRef>,
 }
 
+pub struct Check {
+    pub(crate) name: Variable,
+    pub(crate) circuit: Arc<Circuit>,
+
+    pub(crate) inputs: Vec<VariableRef>,
+    pub(crate) outputs: Vec<VariableRef>,
+
+    pub(crate) body: Vec<Statement>,
+}
+
 pub struct Program {
-    pub(crate) circuits: Vec<Rc<Circuit>>,
-    pub(crate) tests: Vec<Rc<Process>>,
+    pub(crate) circuits: Vec<Arc<Circuit>>,
+    pub(crate) tests: Vec<Arc<Process>>,
+    pub(crate) checks: Vec<Arc<Check>>,
 }
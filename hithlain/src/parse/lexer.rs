@@ -61,6 +61,42 @@ pub enum Token {
     #[display(fmt = "assert")]
     Assert,
 
+    #[token("cover")]
+    #[display(fmt = "cover")]
+    Cover,
+
+    #[token("always")]
+    #[display(fmt = "always")]
+    Always,
+
+    #[token("stable")]
+    #[display(fmt = "stable")]
+    Stable,
+
+    #[token("eventually")]
+    #[display(fmt = "eventually")]
+    Eventually,
+
+    #[token("from")]
+    #[display(fmt = "from")]
+    From,
+
+    #[token("to")]
+    #[display(fmt = "to")]
+    To,
+
+    #[token("during")]
+    #[display(fmt = "during")]
+    During,
+
+    #[token("within")]
+    #[display(fmt = "within")]
+    Within,
+
+    #[token("force")]
+    #[display(fmt = "force")]
+    Force,
+
     #[token("at")]
     #[display(fmt = "absolute time specification")]
     At,
@@ -81,6 +117,14 @@ pub enum Token {
     #[display(fmt = "process")]
     Process,
 
+    #[token("check")]
+    #[display(fmt = "check")]
+    Check,
+
+    #[token("for")]
+    #[display(fmt = "for")]
+    For,
+
     #[token(":")]
     #[display(fmt = ":")]
     Colon,
@@ -90,6 +134,9 @@ pub enum Token {
     #[token(",")]
     #[display(fmt = ",")]
     Comma,
+    #[token(".")]
+    #[display(fmt = ".")]
+    Dot,
 
     #[token("{")]
     #[display(fmt = "{{")]
@@ -98,6 +145,17 @@ pub enum Token {
     #[display(fmt = "}}")]
     RBrace,
 
+    #[token("[")]
+    #[display(fmt = "[")]
+    LBracket,
+    #[token("]")]
+    #[display(fmt = "]")]
+    RBracket,
+
+    #[token("#")]
+    #[display(fmt = "#")]
+    Hash,
+
     #[token("(")]
     #[display(fmt = "(")]
     LParen,
@@ -0,0 +1,190 @@
+use crate::parse::desugared_ast::Statement;
+use crate::parse::scope::{VariableRef, VariableType};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("combinational loop: {}", path)]
+#[diagnostic(help(
+    "every signal in this loop depends on itself without any delay, so it can never settle"
+))]
+pub struct CombinationalLoop {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("part of the loop")]
+    span: SourceSpan,
+
+    path: String,
+}
+
+/// For every variable, the variables that change (without delay) when it changes.
+struct Netlist {
+    order: Vec<VariableRef>,
+    edges: HashMap<VariableRef, Vec<VariableRef>>,
+}
+
+impl Netlist {
+    fn new<'a>(statements: impl IntoIterator<Item = &'a Statement>) -> Self {
+        let mut res = Self {
+            order: Vec::new(),
+            edges: HashMap::new(),
+        };
+
+        for s in statements {
+            if let Statement::Custom {
+                inputs,
+                circuit,
+                into,
+                ..
+            } = s
+            {
+                // only the outputs of the instance that actually depend on an input
+                for (output, dependencies) in into.iter().zip(&circuit.output_dependencies) {
+                    for &i in dependencies {
+                        res.add_edge(&inputs[i], output);
+                    }
+                }
+            } else if let Statement::Delayed(..) = s {
+                // a delay breaks the loop, the signal settles in between
+            } else {
+                for from in s.reads() {
+                    for to in s.writes() {
+                        res.add_edge(from, to);
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    fn add_edge(&mut self, from: &VariableRef, to: &VariableRef) {
+        if !self.edges.contains_key(from) {
+            self.order.push(from.clone());
+        }
+
+        self.edges.entry(from.clone()).or_default().push(to.clone());
+    }
+
+    fn successors(&self, v: &VariableRef) -> &[VariableRef] {
+        self.edges.get(v).map_or(&[], Vec::as_slice)
+    }
+
+    /// Depth first search for a cycle. `on_stack` maps every visited variable
+    /// to whether it is on the current path.
+    #[allow(clippy::mutable_key_type)]
+    fn find_cycle(
+        &self,
+        v: &VariableRef,
+        on_stack: &mut HashMap<VariableRef, bool>,
+        path: &mut Vec<VariableRef>,
+    ) -> Option<Vec<VariableRef>> {
+        on_stack.insert(v.clone(), true);
+        path.push(v.clone());
+
+        for next in self.successors(v) {
+            match on_stack.get(next) {
+                Some(true) => {
+                    let start = path.iter().position(|i| i == next).expect("on the path");
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(next.clone());
+                    return Some(cycle);
+                }
+                Some(false) => {}
+                None => {
+                    if let Some(cycle) = self.find_cycle(next, on_stack, path) {
+                        return Some(cycle);
+                    }
+                }
+            }
+        }
+
+        path.pop();
+        on_stack.insert(v.clone(), false);
+        None
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn reachable(&self, from: &VariableRef, seen: &mut HashSet<VariableRef>) {
+        for next in self.successors(from) {
+            if seen.insert(next.clone()) {
+                self.reachable(next, seen);
+            }
+        }
+    }
+}
+
+/// Makes sure no variable depends on itself through a chain of statements without delay.
+#[allow(clippy::mutable_key_type)]
+pub fn find_combinational_loop<'a>(
+    statements: impl IntoIterator<Item = &'a Statement>,
+) -> Result<(), CombinationalLoop> {
+    let netlist = Netlist::new(statements);
+    let mut on_stack = HashMap::new();
+
+    for v in &netlist.order {
+        if on_stack.contains_key(v) {
+            continue;
+        }
+
+        if let Some(cycle) = netlist.find_cycle(v, &mut on_stack, &mut Vec::new()) {
+            // temporaries don't mean anything to the user
+            let named: Vec<_> = cycle
+                .iter()
+                .filter(|i| i.0.variable_type != VariableType::Temp)
+                .collect();
+
+            let span = named
+                .iter()
+                .find_map(|i| i.0.variable.1.clone())
+                .expect("a loop goes through at least one named variable");
+
+            return Err(CombinationalLoop {
+                src: span.source().clone().into(),
+                span: span.into(),
+                path: named
+                    .iter()
+                    .map(|i| i.0.variable.0.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// For every output, the indices of the inputs it depends on without delay.
+/// Must only be used on statements without combinational loops.
+#[must_use]
+#[allow(clippy::mutable_key_type)]
+pub fn output_dependencies(
+    inputs: &[VariableRef],
+    outputs: &[VariableRef],
+    statements: &[Statement],
+) -> Vec<Vec<usize>> {
+    let netlist = Netlist::new(statements);
+
+    let reachable: Vec<_> = inputs
+        .iter()
+        .map(|i| {
+            let mut seen = HashSet::new();
+            netlist.reachable(i, &mut seen);
+            seen
+        })
+        .collect();
+
+    outputs
+        .iter()
+        .map(|o| {
+            reachable
+                .iter()
+                .enumerate()
+                .filter(|(_, seen)| seen.contains(o))
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .collect()
+}
@@ -2,6 +2,7 @@ pub mod ast;
 pub mod desugar;
 pub mod desugared_ast;
 pub mod lexer;
+pub mod loops;
 pub mod parser;
 pub mod scope;
 pub mod source;
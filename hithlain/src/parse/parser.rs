@@ -1,6 +1,7 @@
 use crate::parse::ast::{
-    Assignment, Atom, BinaryAction, Circuit, Constant, Expr, NaryAction, Process, Program,
-    Statement, StatementOrTime, Test, TimeSpec, UnaryAction, Variable,
+    Assignment, Atom, Attribute, BinaryAction, Check, Circuit, Constant, CoverBin, Expr,
+    HierarchicalPath, NaryAction, Process, Program, Statement, StatementOrTime, Temporal, Test,
+    TimeSpec, UnaryAction, Variable,
 };
 use crate::parse::lexer::{Token, TokenIterator, TokenStream};
 use crate::parse::span::Span;
@@ -21,6 +22,43 @@ pub enum ParseError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     RightSideOfExpr(#[from] RightSideOfExpr),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnknownAttribute(#[from] UnknownAttribute),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    MisplacedAttribute(#[from] MisplacedAttribute),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("unknown attribute {}", name)]
+#[diagnostic(help(
+    "valid attributes are should_fail, ignore, timeout(<time>), tag(<name>) and delay(<time>)"
+))]
+pub struct UnknownAttribute {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("here")]
+    span: SourceSpan,
+
+    name: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("attribute {} can't be used on a {}", name, target)]
+#[diagnostic(help("delay can only be used on circuits, all other attributes only on tests"))]
+pub struct MisplacedAttribute {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("here")]
+    span: SourceSpan,
+
+    name: &'static str,
+    target: &'static str,
 }
 
 #[derive(Error, Debug, Diagnostic)]
@@ -64,6 +102,16 @@ pub struct UnexpectedEnd {
     expected: String,
 }
 
+fn misplaced_attribute(attribute: &Attribute, spn: &Span, target: &'static str) -> ParseError {
+    MisplacedAttribute {
+        src: spn.source().clone().into(),
+        span: spn.clone().into(),
+        name: attribute.name(),
+        target,
+    }
+    .into()
+}
+
 pub struct Parser {
     tokens: TokenIterator,
     previous_span: Option<Span>,
@@ -81,11 +129,11 @@ fn or_unexpected_end<T>(
     } else {
         let span = span();
 
-        return Err(UnexpectedEnd {
+        Err(UnexpectedEnd {
             expected: description.as_ref().to_string(),
             span: span.clone().into(),
             src: span.source().clone().into(),
-        });
+        })
     }
 }
 
@@ -164,13 +212,51 @@ impl Parser {
             return Ok(());
         }
 
-        return Err(UnexpectedToken {
+        Err(UnexpectedToken {
             expected: description.unwrap_or(format!("`{}`", t)),
             found: tkn.clone(),
             span: spn.clone().into(),
             src: spn.source().clone().into(),
         }
-        .into());
+        .into())
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        let spn = self.current_span();
+        let (tkn, spn) = or_unexpected_end(self.peek().cloned(), keyword, || spn)?;
+
+        if let Token::Name(ref name) = tkn {
+            if name == keyword {
+                self.next();
+                return Ok(());
+            }
+        }
+
+        Err(UnexpectedToken {
+            expected: format!("`{}`", keyword),
+            found: tkn,
+            span: spn.clone().into(),
+            src: spn.source().clone().into(),
+        }
+        .into())
+    }
+
+    pub fn parse_time(&mut self) -> Result<u64, ParseError> {
+        let spn = self.current_span();
+        let (tkn, spn) = or_unexpected_end(self.peek().cloned(), "time", || spn)?;
+
+        if let Token::Time(a) = tkn {
+            self.next();
+            Ok(a)
+        } else {
+            Err(UnexpectedToken {
+                expected: "time".to_string(),
+                found: tkn,
+                span: spn.clone().into(),
+                src: spn.source().clone().into(),
+            }
+            .into())
+        }
     }
 
     pub fn parse_variable(&mut self, description: Option<String>) -> Result<Variable, ParseError> {
@@ -183,17 +269,16 @@ impl Parser {
         )?;
 
         if let Token::Name(name) = tok {
-            let name = name;
             self.next();
             Ok(Variable(name, Some(spn)))
         } else {
-            return Err(UnexpectedToken {
+            Err(UnexpectedToken {
                 expected: description.unwrap_or_else(|| "variable".to_string()),
                 found: tok,
                 span: spn.clone().into(),
                 src: spn.source().clone().into(),
             }
-            .into());
+            .into())
         }
     }
 
@@ -228,6 +313,12 @@ impl Parser {
         dbg!(tok);
 
         if let Ok(i) = self.parse_variable(None) {
+            if let Some((Token::Dot, _)) = self.peek() {
+                return Ok(Expr::Atom(Atom::Hierarchical(
+                    self.parse_hierarchical_path(i)?,
+                )));
+            }
+
             Ok(Expr::Atom(Atom::Variable(i)))
         } else if let Ok(i) = self.parse_constant(None) {
             Ok(Expr::Atom(Atom::Constant(i)))
@@ -241,18 +332,40 @@ impl Parser {
 
                 return Ok(Expr::Atom(res));
             }
-            return Err(UnexpectedToken {
+            Err(UnexpectedToken {
                 expected: "variable, constant value or parenthesized expression".to_string(),
                 found: self.current_token(),
                 span: self.current_span().into(),
                 src: self.current_span().source().clone().into(),
             }
-            .into());
+            .into())
+        }
+    }
+
+    /// Parses the rest of a path like `cpu.alu.carry`, after its first element.
+    pub fn parse_hierarchical_path(
+        &mut self,
+        first: Variable,
+    ) -> Result<HierarchicalPath, ParseError> {
+        let mut instances = vec![first];
+
+        while let Some((Token::Dot, _)) = self.peek() {
+            self.next();
+            instances.push(self.parse_variable(Some("instance or signal name".to_string()))?);
         }
+
+        let variable = instances.pop().expect("path has at least one element");
+
+        Ok(HierarchicalPath {
+            instances,
+            variable,
+        })
     }
 
     pub fn parse_binary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.peek().map(|(_, spn)| spn.clone());
         let mut root = self.parse_atom()?;
+        let start = start.expect("an atom was parsed");
 
         while let Some((tok, spn)) = self.peek().cloned() {
             let op = match tok {
@@ -284,6 +397,7 @@ impl Parser {
                 a: Box::new(root),
                 b: Box::new(right_side),
                 action: op,
+                span: start.merge_with(&self.current_span()),
             };
         }
 
@@ -313,9 +427,15 @@ impl Parser {
 
         self.expect_single_token(&Token::RParen, Some("closing parenthesis".to_string()))?;
 
+        let span = circuit
+            .1
+            .as_ref()
+            .expect("parsed variables have a span")
+            .merge_with(&self.current_span());
         Ok(Expr::NaryOp {
             params,
             action: NaryAction::Custom(circuit),
+            span,
         })
     }
 
@@ -326,8 +446,8 @@ impl Parser {
             }
         }
 
-        if let Some((tok, _)) = self.peek() {
-            if tok == &Token::Not {
+        if let Some((tok, spn)) = self.peek().cloned() {
+            if tok == Token::Not {
                 self.next();
                 self.expect_single_token(&Token::LParen, None)?;
                 let param = self.parse_atom()?;
@@ -336,6 +456,7 @@ impl Parser {
                 return Ok(Expr::NaryOp {
                     params: vec![param],
                     action: NaryAction::UnaryAction(UnaryAction::Not),
+                    span: spn.merge_with(&self.current_span()),
                 });
             }
         }
@@ -348,14 +469,97 @@ impl Parser {
             let spn = spn.clone();
             if tok == &Token::Assert {
                 self.next();
+
+                let kind = match self.peek() {
+                    Some((Token::Always | Token::Stable | Token::Eventually, _)) => {
+                        self.next().map(|(tok, _)| tok)
+                    }
+                    _ => None,
+                };
+
                 let expr = self.parse_expr()?;
+
+                let temporal = match kind {
+                    Some(Token::Always) => {
+                        self.expect_single_token(&Token::From, None)?;
+                        let from = Instant::nanos_from_start(self.parse_time()?);
+                        self.expect_single_token(&Token::To, None)?;
+                        let to = Instant::nanos_from_start(self.parse_time()?);
+
+                        Some(Temporal::Always { from, to })
+                    }
+                    Some(Token::Stable) => {
+                        self.expect_single_token(&Token::During, None)?;
+                        Some(Temporal::Stable(Duration::from_nanos(self.parse_time()?)))
+                    }
+                    Some(Token::Eventually) => {
+                        self.expect_single_token(&Token::Within, None)?;
+                        Some(Temporal::Eventually(Duration::from_nanos(
+                            self.parse_time()?,
+                        )))
+                    }
+                    _ => None,
+                };
+
                 self.expect_single_token(&Token::SemiColon, None)?;
+                let span = spn.merge_with(&self.current_span());
+
+                return Ok(match temporal {
+                    Some(temporal) => Statement::TemporalAssert {
+                        expr,
+                        span,
+                        temporal,
+                    },
+                    None => Statement::Assert { expr, span },
+                });
+            }
+        }
 
-                return Ok(Statement::Assert {
-                    expr,
+        if let Some((Token::Cover, spn)) = self.peek() {
+            let spn = spn.clone();
+            self.next();
+
+            let name = match self.peek_2() {
+                Some([(Token::Name(name), _), (Token::Colon, _)]) => {
+                    self.next();
+                    self.next();
+                    Some(name)
+                }
+                _ => None,
+            };
+
+            let start = self.peek().map(|(_, i)| i.clone());
+            let expr = self.parse_expr()?;
+            let condition = match start {
+                Some(start) => start.merge_with(&self.current_span()),
+                None => self.current_span(),
+            };
+
+            self.expect_single_token(&Token::SemiColon, None)?;
+
+            return Ok(Statement::Cover {
+                expr,
+                bin: CoverBin {
+                    name: name.unwrap_or_else(|| condition.text().to_string()),
                     span: spn.merge_with(&self.current_span()),
-                });
+                },
+            });
+        }
+
+        if let Some((Token::Force, _)) = self.peek() {
+            self.next();
+
+            let first = self.parse_variable(Some("instance name".to_string()))?;
+            let path = self.parse_hierarchical_path(first)?;
+            if path.instances.is_empty() {
+                self.expect_single_token(&Token::Dot, None)?;
             }
+
+            self.expect_single_token(&Token::Assignment, None)?;
+            let value = self.parse_constant(Some("constant value to force".to_string()))?;
+            self.expect_single_token(&Token::SemiColon, None)?;
+
+            return Ok(Statement::Force { path, value });
         }
 
         let mut vars = vec![self.parse_variable(Some(
@@ -383,12 +587,23 @@ impl Parser {
 
         let expr = self.parse_expr()?;
 
+        let delay = if let Some((Token::After, _)) = self.peek() {
+            self.next();
+            Some(Duration::from_nanos(self.parse_time()?))
+        } else {
+            None
+        };
+
         self.expect_single_token(
             &Token::SemiColon,
-            Some("`;` or binary operator".to_string()),
+            Some("`;`, `after` or binary operator".to_string()),
         )?;
 
-        Ok(Statement::Assignment(Assignment { into: vars, expr }))
+        Ok(Statement::Assignment(Assignment {
+            into: vars,
+            expr,
+            delay,
+        }))
     }
 
     pub fn parse_statement_or_time(&mut self) -> Result<StatementOrTime, ParseError> {
@@ -480,7 +695,7 @@ impl Parser {
         Ok((inputs, outputs))
     }
 
-    pub fn parse_circuit(&mut self) -> Result<Circuit, ParseError> {
+    pub fn parse_circuit(&mut self, delay: Option<Duration>) -> Result<Circuit, ParseError> {
         self.next();
         let name = self.parse_variable(Some("circuit name".to_string()))?;
 
@@ -528,6 +743,7 @@ impl Parser {
             inputs,
             outputs,
             body,
+            delay,
         })
     }
 
@@ -558,7 +774,53 @@ impl Parser {
         })
     }
 
-    pub fn parse_test(&mut self) -> Result<Test, ParseError> {
+    pub fn parse_attribute(&mut self) -> Result<Attribute, ParseError> {
+        self.next();
+        self.expect_single_token(&Token::LBracket, None)?;
+
+        let name = self.parse_variable(Some("attribute name".to_string()))?;
+
+        let attribute = match name.0.as_str() {
+            "should_fail" => Attribute::ShouldFail,
+            "ignore" => Attribute::Ignore,
+            "timeout" => {
+                self.expect_single_token(&Token::LParen, None)?;
+                let time = self.parse_time()?;
+                self.expect_single_token(&Token::RParen, None)?;
+
+                Attribute::Timeout(Duration::from_nanos(time))
+            }
+            "tag" => {
+                self.expect_single_token(&Token::LParen, None)?;
+                let tag = self.parse_variable(Some("tag name".to_string()))?;
+                self.expect_single_token(&Token::RParen, None)?;
+
+                Attribute::Tag(tag.0)
+            }
+            "delay" => {
+                self.expect_single_token(&Token::LParen, None)?;
+                let time = self.parse_time()?;
+                self.expect_single_token(&Token::RParen, None)?;
+
+                Attribute::Delay(Duration::from_nanos(time))
+            }
+            _ => {
+                let spn = name.1.expect("parsed variables have a span");
+                return Err(UnknownAttribute {
+                    src: spn.source().clone().into(),
+                    span: spn.into(),
+                    name: name.0,
+                }
+                .into());
+            }
+        };
+
+        self.expect_single_token(&Token::RBracket, None)?;
+
+        Ok(attribute)
+    }
+
+    pub fn parse_test(&mut self, attributes: Vec<Attribute>) -> Result<Test, ParseError> {
         self.next();
         let name = self.parse_variable(Some("test name".to_string()))?;
 
@@ -577,24 +839,100 @@ impl Parser {
             }
         }
 
-        Ok(Test { name, body })
+        Ok(Test {
+            name,
+            attributes,
+            body,
+        })
+    }
+
+    pub fn parse_check(&mut self) -> Result<Check, ParseError> {
+        self.next();
+        let circuit = self.parse_variable(Some("name of the circuit to check".to_string()))?;
+
+        self.expect_single_token(&Token::For, None)?;
+        self.expect_keyword("all")?;
+        self.expect_keyword("inputs")?;
+        self.expect_single_token(&Token::LBrace, None)?;
+
+        let mut body = Vec::new();
+
+        loop {
+            let span = self.current_span();
+            match or_unexpected_end(self.peek(), "statement or `}`", || span)? {
+                (Token::RBrace, _) => {
+                    self.next();
+                    break;
+                }
+                (_, _) => body.push(self.parse_statement()?),
+            }
+        }
+
+        Ok(Check { circuit, body })
     }
 
     pub fn parse_program(&mut self) -> Result<Program, ParseError> {
         let mut circuits = Vec::new();
         let mut tests = Vec::new();
         let mut processes = Vec::new();
+        let mut checks = Vec::new();
         while let Some((tok, spn)) = self.peek() {
             match tok {
                 Token::Circuit => {
-                    circuits.push(self.parse_circuit()?);
+                    circuits.push(self.parse_circuit(None)?);
                 }
                 Token::Test => {
-                    tests.push(self.parse_test()?);
+                    tests.push(self.parse_test(Vec::new())?);
+                }
+                Token::Hash => {
+                    let mut attributes = Vec::new();
+                    while let Some((Token::Hash, spn)) = self.peek() {
+                        let spn = spn.clone();
+                        attributes.push((self.parse_attribute()?, spn));
+                    }
+
+                    let spn = self.current_span();
+                    match or_unexpected_end(self.peek().cloned(), "test or circuit", || spn)? {
+                        (Token::Test, _) => {
+                            let mut test_attributes = Vec::new();
+                            for (attribute, spn) in attributes {
+                                if let Attribute::Delay(_) = attribute {
+                                    return Err(misplaced_attribute(&attribute, &spn, "test"));
+                                }
+                                test_attributes.push(attribute);
+                            }
+
+                            tests.push(self.parse_test(test_attributes)?);
+                        }
+                        (Token::Circuit, _) => {
+                            let mut delay = None;
+                            for (attribute, spn) in attributes {
+                                if let Attribute::Delay(d) = attribute {
+                                    delay = Some(d);
+                                } else {
+                                    return Err(misplaced_attribute(&attribute, &spn, "circuit"));
+                                }
+                            }
+
+                            circuits.push(self.parse_circuit(delay)?);
+                        }
+                        (tok, spn) => {
+                            return Err(UnexpectedToken {
+                                expected: "test or circuit after attributes".to_string(),
+                                found: tok,
+                                span: spn.clone().into(),
+                                src: spn.source().clone().into(),
+                            }
+                            .into());
+                        }
+                    }
                 }
                 Token::Process => {
                     processes.push(self.parse_process()?);
                 }
+                Token::Check => {
+                    checks.push(self.parse_check()?);
+                }
                 Token::Error => unreachable!(),
                 i => {
                     return Err(UnexpectedToken {
@@ -612,6 +950,7 @@ impl Parser {
             circuits,
             processes,
             tests,
+            checks,
         })
     }
 }
@@ -619,9 +958,11 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use crate::error::NiceUnwrap;
+    use crate::parse::ast::{Attribute, Statement};
     use crate::parse::lexer::lex;
-    use crate::parse::parser::Parser;
+    use crate::parse::parser::{ParseError, Parser};
     use crate::parse::source::Source;
+    use crate::time::Duration;
 
     #[test]
     fn test_smoke() {
@@ -654,6 +995,122 @@ mod tests {
         parser.parse_program().nice_unwrap_panic();
     }
 
+    #[test]
+    fn check_block() {
+        let src = "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or ((a xor b) and c_in);
+        }
+
+        check add for all inputs {
+            assert o == a xor b xor c_in;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        assert_eq!(parsed.checks.len(), 1);
+        assert_eq!(parsed.checks[0].body.len(), 1);
+    }
+
+    #[test]
+    fn temporal_asserts() {
+        let src = "
+        test main {
+            at 0ns:
+                assert always (o == a xor b) from 0ns to 50ns;
+                assert stable data during 10ns;
+                assert eventually ack within 20ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        assert_eq!(parsed.tests[0].body.len(), 4);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let src = "
+        #[should_fail]
+        #[timeout(1us)]
+        #[tag(slow)]
+        test main {}
+
+        #[ignore]
+        test other {}
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        assert_eq!(
+            parsed.tests[0].attributes,
+            vec![
+                Attribute::ShouldFail,
+                Attribute::Timeout(Duration::from_nanos(1_000)),
+                Attribute::Tag("slow".to_string())
+            ]
+        );
+        assert_eq!(parsed.tests[1].attributes, vec![Attribute::Ignore]);
+    }
+
+    #[test]
+    fn delays() {
+        let src = "
+        #[delay(1ns)]
+        circuit slow: a b -> o {
+            o = a and b after 2ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        assert_eq!(parsed.circuits[0].delay, Some(Duration::from_nanos(1)));
+        match &parsed.circuits[0].body[0] {
+            Statement::Assignment(a) => assert_eq!(a.delay, Some(Duration::from_nanos(2))),
+            _ => panic!("expected an assignment"),
+        }
+
+        let src = "
+        #[delay(1ns)]
+        test main {}
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParseError::MisplacedAttribute(_))
+        ));
+    }
+
+    #[test]
+    fn hierarchical_paths() {
+        let src = "
+        test main {
+            at 0ns:
+                assert add.x == 1;
+                force cpu.alu.carry = 0;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        assert_eq!(parsed.tests[0].body.len(), 3);
+    }
+
     #[test]
     fn number_constants() {
         let src = "
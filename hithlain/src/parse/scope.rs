@@ -2,8 +2,8 @@ use crate::parse::ast::Variable;
 use derivative::Derivative;
 use miette::{Diagnostic, NamedSource, SourceSpan};
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug, Diagnostic)]
@@ -47,7 +47,7 @@ pub struct InnerVariableRef {
 impl Eq for InnerVariableRef {}
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct VariableRef(pub(crate) Rc<InnerVariableRef>);
+pub struct VariableRef(pub(crate) Arc<InnerVariableRef>);
 
 pub struct Scope {
     pub(crate) variables: HashMap<Variable, VariableRef>,
@@ -122,7 +122,7 @@ impl Scope {
             });
         }
 
-        let vr = VariableRef(Rc::new(InnerVariableRef {
+        let vr = VariableRef(Arc::new(InnerVariableRef {
             variable: variable.clone(),
             variable_type,
             read: AtomicBool::new(false),
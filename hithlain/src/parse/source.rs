@@ -77,7 +77,7 @@ impl Source {
 
     #[must_use]
     pub fn text(&self) -> &str {
-        &*self
+        self
     }
 }
 
@@ -2,6 +2,9 @@ use crate::parse::source::Source;
 use logos::Span as LogosSpan;
 use miette::SourceSpan;
 
+/// A span by file and position in it, which orders spans in the order they are written in.
+pub(crate) type SpanKey = (String, usize, usize);
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Span {
     start: usize,
@@ -23,6 +26,34 @@ impl Span {
         &self.source
     }
 
+    /// The byte offset the span starts at.
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The byte offset just after the span.
+    #[must_use]
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+
+    pub(crate) fn key(&self) -> SpanKey {
+        (self.source.name().to_string(), self.start, self.end())
+    }
+
+    /// The source text the span covers.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.source[self.start..self.end()]
+    }
+
+    /// The line the span starts on, counting from 1.
+    #[must_use]
+    pub fn line(&self) -> usize {
+        self.source[..self.start].matches('\n').count() + 1
+    }
+
     #[must_use]
     pub fn merge_with(&self, other: &Self) -> Self {
         let start = self.start.min(other.start);
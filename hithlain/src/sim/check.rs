@@ -0,0 +1,182 @@
+use crate::parse::ast::Constant;
+use crate::parse::desugared_ast::{Check, Process, Statement, TestAttributes, TimedBlock};
+use crate::parse::span::Span;
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::linked_ast as l;
+use crate::sim::simulation::{AssertionError, Simulation};
+use crate::time::{Duration, Instant};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("check of circuit {} failed", circuit)]
+#[diagnostic(help("counterexample: {}", assignment))]
+pub struct CounterExample {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("doesn't hold for this input")]
+    span: SourceSpan,
+
+    circuit: String,
+    pub(crate) assignment: String,
+}
+
+/// The value of input `index` (out of `inputs`) in the `assignment`th combination.
+/// The first input is the most significant bit, so combinations are enumerated
+/// in the order you would write them in a truth table.
+fn input_value(assignment: u64, index: usize, inputs: usize) -> bool {
+    (assignment >> (inputs - index - 1)) & 1 == 1
+}
+
+/// How far apart the combinations of inputs are applied. Every combination gets
+/// enough time for the circuit to settle, with assertions checked at the end.
+fn period(check: &Check) -> u64 {
+    check.circuit.max_delay().nanos() + 1
+}
+
+/// Turns a check into a process that applies the first combination of inputs to the circuit.
+/// [`Combinations`] applies the others while it is simulated, one period apart. The combination
+/// that is being checked at any time is therefore equal to the number of periods since the start.
+#[must_use]
+pub fn check_to_process(check: &Check) -> Process {
+    let inputs = check.inputs.len();
+    let settle = Duration::from_nanos(period(check) - 1);
+
+    let (asserts, rest): (Vec<_>, Vec<_>) = check
+        .body
+        .iter()
+        .cloned()
+        .partition(|i| matches!(i, Statement::Assert(..)));
+
+    // the circuit (and everything computed from its outputs) only has to be
+    // created once, after that only the inputs and assertions are repeated.
+    let mut block: Vec<_> = check
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, i)| Statement::Set(i.clone(), Constant::Bit(input_value(0, index, inputs))))
+        .collect();
+    block.extend(rest);
+
+    Process {
+        name: check.name.clone(),
+        timed_blocks: vec![
+            TimedBlock {
+                time: Instant::START,
+                block,
+            },
+            TimedBlock {
+                time: Instant::START.after(&settle),
+                block: asserts,
+            },
+        ],
+        attributes: TestAttributes::default(),
+        inputs: vec![],
+        outputs: vec![],
+    }
+}
+
+/// Schedules the combinations of inputs after the first one while a check is simulated,
+/// so they don't all have to exist up front.
+pub(crate) struct Combinations {
+    /// in the order of the inputs of the circuit
+    inputs: Vec<UniqueVariableRef>,
+    asserts: Vec<(UniqueVariableRef, Span)>,
+    period: u64,
+    next: u64,
+    total: u64,
+}
+
+impl Combinations {
+    /// Finds the inputs and assertions of the first combination in the linked check.
+    pub(crate) fn new(check: &Check, process: &l::Process) -> Self {
+        let period = period(check);
+        let asserted_at = Instant::START
+            .after(&Duration::from_nanos(period - 1))
+            .observe();
+
+        let mut inputs = vec![None; check.inputs.len()];
+        let mut asserts = Vec::new();
+        for i in &process.conditions {
+            match i {
+                l::Condition::AtTime { time, run } if *time == Instant::START => {
+                    if let l::Statement::Set(v, _) = &**run {
+                        let index = check
+                            .inputs
+                            .iter()
+                            .position(|i| i.0.variable == v.original.variable);
+                        if let (Some(index), 1) = (index, v.original.path.len()) {
+                            inputs[index] = Some(v.clone());
+                        }
+                    }
+                }
+                l::Condition::AtTime { time, run } if *time == asserted_at => {
+                    if let l::Statement::Assert(v, span) = &**run {
+                        asserts.push((v.clone(), span.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            inputs: inputs
+                .into_iter()
+                .map(|i| i.expect("every input is set in the first combination"))
+                .collect(),
+            asserts,
+            period,
+            next: 1,
+            total: 1 << check.inputs.len(),
+        }
+    }
+
+    /// Schedules the next combination once everything before it has run.
+    pub(crate) fn advance(&mut self, simulation: &mut Simulation) {
+        let start = self.next * self.period;
+        if self.next >= self.total || simulation.next_instant().is_some_and(|i| i.nanos() < start) {
+            return;
+        }
+
+        let time = Instant::nanos_from_start(start);
+        for (index, v) in self.inputs.iter().enumerate() {
+            let value = input_value(self.next, index, self.inputs.len());
+            simulation.schedule(time, l::Statement::Set(v.clone(), Constant::Bit(value)));
+        }
+
+        let asserted_at = time.after(&Duration::from_nanos(self.period - 1)).observe();
+        for (v, span) in &self.asserts {
+            simulation.schedule(asserted_at, l::Statement::Assert(v.clone(), span.clone()));
+        }
+
+        self.next += 1;
+    }
+}
+
+pub fn counterexample(check: &Arc<Check>, error: AssertionError) -> CounterExample {
+    let assignment = error.time.nanos() / period(check);
+    let inputs = check.inputs.len();
+
+    let assignment = check
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, i)| {
+            format!(
+                "{} = {}",
+                i.0.variable.0,
+                u8::from(input_value(assignment, index, inputs))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    CounterExample {
+        src: error.src,
+        span: error.span,
+        circuit: check.name.0.clone(),
+        assignment,
+    }
+}
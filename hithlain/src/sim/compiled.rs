@@ -0,0 +1,447 @@
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::linked_ast::{BinaryBuiltin, Statement};
+use crate::sim::value::Value;
+use miette::Diagnostic;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(
+    "the compiled engine can't simulate a loop without delay through {}",
+    signals
+)]
+#[diagnostic(help("use the event driven engine, which detects whether the loop oscillates"))]
+pub struct NotLevelisable {
+    signals: String,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Not,
+    And,
+    Or,
+    Nand,
+    Nor,
+    Xor,
+    Xnor,
+    Move,
+}
+
+/// One combinational statement, working on slots instead of variables.
+/// Unary operations only use `a`.
+struct Instruction {
+    statement: Arc<Statement>,
+    op: Op,
+    a: usize,
+    b: usize,
+    into: usize,
+}
+
+impl Instruction {
+    fn evaluate(&self, values: &[Option<bool>]) -> Option<bool> {
+        let a = values[self.a]?;
+        let b = || values[self.b];
+
+        Some(match self.op {
+            Op::Not => !a,
+            Op::Move => a,
+            Op::And => a & b()?,
+            Op::Or => a | b()?,
+            Op::Nand => !(a & b()?),
+            Op::Nor => !(a | b()?),
+            Op::Xor => a ^ b()?,
+            Op::Xnor => !(a ^ b()?),
+        })
+    }
+}
+
+/// Whether the compiled engine evaluates a statement, instead of scheduling it as an event.
+#[must_use]
+pub fn is_combinational(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::Not { .. }
+            | Statement::And(_)
+            | Statement::Or(_)
+            | Statement::Nand(_)
+            | Statement::Nor(_)
+            | Statement::Xor(_)
+            | Statement::Xnor(_)
+            | Statement::Move(_, _)
+    )
+}
+
+/// The combinational statements of a simulation, ordered so that every statement
+/// comes after the statements it depends on. After an input changes,
+/// everything it affects is evaluated in a single pass over the instructions,
+/// instead of through one event per statement.
+pub struct Netlist {
+    slots: HashMap<UniqueVariableRef, usize>,
+    pub(crate) variables: Vec<UniqueVariableRef>,
+    values: Vec<Option<bool>>,
+
+    instructions: Vec<Instruction>,
+    /// for every slot, the instructions that read it
+    readers: Vec<Vec<usize>>,
+    dirty: Vec<bool>,
+    /// the first dirty instruction, or the number of instructions when none are
+    first_dirty: usize,
+}
+
+impl Netlist {
+    fn slot(&mut self, variable: &UniqueVariableRef) -> usize {
+        if let Some(&i) = self.slots.get(variable) {
+            return i;
+        }
+
+        let slot = self.variables.len();
+        self.slots.insert(variable.clone(), slot);
+        self.variables.push(variable.clone());
+        self.values.push(None);
+        self.readers.push(Vec::new());
+        slot
+    }
+
+    /// Levelises the statements, which must all be combinational.
+    pub fn compile(statements: &[Arc<Statement>]) -> Result<Self, NotLevelisable> {
+        let mut res = Self {
+            slots: HashMap::new(),
+            variables: Vec::new(),
+            values: Vec::new(),
+            instructions: Vec::new(),
+            readers: Vec::new(),
+            dirty: Vec::new(),
+            first_dirty: 0,
+        };
+
+        let mut unordered = Vec::new();
+        for i in statements {
+            let (op, a, b, into) = match &**i {
+                Statement::Not { input, into } => (Op::Not, input, input, into),
+                Statement::Move(into, from) => (Op::Move, from, from, into),
+                Statement::And(BinaryBuiltin { a, b, into }) => (Op::And, a, b, into),
+                Statement::Or(BinaryBuiltin { a, b, into }) => (Op::Or, a, b, into),
+                Statement::Nand(BinaryBuiltin { a, b, into }) => (Op::Nand, a, b, into),
+                Statement::Nor(BinaryBuiltin { a, b, into }) => (Op::Nor, a, b, into),
+                Statement::Xor(BinaryBuiltin { a, b, into }) => (Op::Xor, a, b, into),
+                Statement::Xnor(BinaryBuiltin { a, b, into }) => (Op::Xnor, a, b, into),
+                _ => unreachable!("only combinational statements are compiled"),
+            };
+
+            unordered.push(Instruction {
+                statement: i.clone(),
+                op,
+                a: res.slot(a),
+                b: res.slot(b),
+                into: res.slot(into),
+            });
+        }
+
+        // Kahn's algorithm over the slots: a statement can run once all its inputs are known
+        let mut writers: Vec<Vec<usize>> = vec![Vec::new(); res.variables.len()];
+        let mut waiting = vec![0; unordered.len()];
+        for (index, i) in unordered.iter().enumerate() {
+            writers[i.into].push(index);
+        }
+        for (index, i) in unordered.iter().enumerate() {
+            waiting[index] = writers[i.a].len() + if i.b == i.a { 0 } else { writers[i.b].len() };
+        }
+
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); unordered.len()];
+        for (index, i) in unordered.iter().enumerate() {
+            for &w in &writers[i.a] {
+                dependents[w].push(index);
+            }
+            if i.b != i.a {
+                for &w in &writers[i.b] {
+                    dependents[w].push(index);
+                }
+            }
+        }
+
+        let mut ready: Vec<_> = (0..unordered.len()).filter(|&i| waiting[i] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            for &d in &dependents[i] {
+                waiting[d] -= 1;
+                if waiting[d] == 0 {
+                    ready.push(d);
+                }
+            }
+        }
+
+        if order.len() != unordered.len() {
+            let mut signals: Vec<_> = (0..unordered.len())
+                .filter(|&i| waiting[i] != 0)
+                .map(|i| &res.variables[unordered[i].into])
+                .filter(|i| !i.generated)
+                .map(UniqueVariableRef::name)
+                .collect();
+            signals.sort();
+            signals.dedup();
+
+            return Err(NotLevelisable {
+                signals: signals.join(", "),
+            });
+        }
+
+        let mut unordered: Vec<_> = unordered.into_iter().map(Some).collect();
+        res.instructions = order
+            .into_iter()
+            .map(|i| {
+                unordered[i]
+                    .take()
+                    .expect("every instruction is ordered once")
+            })
+            .collect();
+
+        for (index, i) in res.instructions.iter().enumerate() {
+            res.readers[i.a].push(index);
+            if i.b != i.a {
+                res.readers[i.b].push(index);
+            }
+        }
+        res.dirty = vec![false; res.instructions.len()];
+        res.first_dirty = res.instructions.len();
+
+        Ok(res)
+    }
+
+    /// The slot of a variable, if any compiled statement uses it.
+    #[must_use]
+    pub fn slot_of(&self, variable: &UniqueVariableRef) -> Option<usize> {
+        self.slots.get(variable).copied()
+    }
+
+    /// Sets a slot from the outside, marking everything that reads it for evaluation.
+    pub fn set(&mut self, slot: usize, value: &Value) {
+        let Value::Bit(b) = value;
+        self.values[slot] = Some(*b);
+
+        for &i in &self.readers[slot] {
+            self.dirty[i] = true;
+            self.first_dirty = self.first_dirty.min(i);
+        }
+    }
+
+    /// Whether any slot was set since the last propagation.
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.first_dirty < self.instructions.len()
+    }
+
+    /// Forgets all values, and takes them from the store instead. Everything is
+    /// evaluated again on the next propagation, since the store may not have settled yet.
+    #[allow(clippy::mutable_key_type)]
+    pub fn load(&mut self, store: &HashMap<UniqueVariableRef, Value>) {
+        for (slot, variable) in self.variables.iter().enumerate() {
+            self.values[slot] = store.get(variable).map(|Value::Bit(b)| *b);
+        }
+        self.dirty.iter_mut().for_each(|i| *i = true);
+        self.first_dirty = 0;
+    }
+
+    /// Evaluates every instruction affected by the slots set since the last propagation,
+    /// in order. `is_forced` slots keep their value, `evaluated` is called for every statement
+    /// that's evaluated. Returns the slots that changed.
+    pub fn propagate(
+        &mut self,
+        is_forced: impl Fn(&UniqueVariableRef) -> bool,
+        mut evaluated: impl FnMut(&Statement),
+    ) -> Vec<usize> {
+        let mut changed = Vec::new();
+
+        for index in self.first_dirty..self.instructions.len() {
+            if !self.dirty[index] {
+                continue;
+            }
+            self.dirty[index] = false;
+
+            let instruction = &self.instructions[index];
+            evaluated(&instruction.statement);
+            let value = instruction.evaluate(&self.values);
+            let into = instruction.into;
+
+            if value.is_none() || value == self.values[into] || is_forced(&self.variables[into]) {
+                continue;
+            }
+
+            self.values[into] = value;
+            changed.push(into);
+
+            // readers always come later in the order
+            for &i in &self.readers[into] {
+                self.dirty[i] = true;
+            }
+        }
+        self.first_dirty = self.instructions.len();
+
+        changed
+    }
+
+    #[must_use]
+    pub fn value(&self, slot: usize) -> Option<Value> {
+        self.values[slot].map(Value::Bit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::NiceUnwrap;
+    use crate::generate::ripple_carry_adder;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::sim::config::{Engine, SimulationConfig};
+    use crate::sim::report::TestReport;
+    use crate::sim::{SimulationError, Simulator};
+
+    fn simulator(src: &str, engine: Engine) -> Simulator {
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let config = SimulationConfig {
+            engine,
+            ..SimulationConfig::default()
+        };
+        Simulator::new(desugared, config).nice_unwrap_panic()
+    }
+
+    #[test]
+    fn same_results_as_event_driven() {
+        let src = "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or ((a xor b) and c_in);
+        }
+
+        #[delay(1ns)]
+        circuit chain: a -> o {
+            x = not(a);
+            o = not(x);
+        }
+
+        test adds {
+            o, c_out = add(a, b, 0);
+
+            at 0ns:
+                a = 1;
+                b = 1;
+
+                assert o == 0;
+                assert c_out == 1;
+
+            after 5ns:
+                b = 0;
+
+                assert o == 1;
+                assert always (o == (a xor b)) from 5ns to 10ns;
+        }
+
+        test delays {
+            o = chain(a);
+
+            at 0ns:
+                a = 1;
+            at 1ns:
+                assert o == 0;
+            at 2ns:
+                assert o == 1;
+        }
+
+        #[should_fail]
+        test fails {
+            o, c_out = add(a, b, 1);
+
+            at 0ns:
+                a = 1;
+                b = 0;
+
+                assert o == 1;
+        }
+        ";
+
+        for engine in [Engine::EventDriven, Engine::Compiled] {
+            let s = simulator(src, engine);
+            s.run_test("adds")
+                .and_then(TestReport::into_result)
+                .nice_unwrap_panic();
+            s.run_test("delays")
+                .and_then(TestReport::into_result)
+                .nice_unwrap_panic();
+            s.run_test("fails")
+                .and_then(TestReport::into_result)
+                .nice_unwrap_panic();
+        }
+    }
+
+    #[test]
+    fn random_vectors() {
+        // a ripple carry adder, with every signal compared between both engines
+        let (src, inputs) = ripple_carry_adder(8);
+
+        let event_driven = simulator(&src, Engine::EventDriven);
+        let compiled = simulator(&src, Engine::Compiled);
+        let mut expected = event_driven.instantiate("ripple").nice_unwrap_panic();
+        let mut actual = compiled.instantiate("ripple").nice_unwrap_panic();
+
+        let mut seed = 0x2545_f491_u32;
+        for _ in 0..50 {
+            for input in &inputs {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+
+                expected.poke(input, seed & 1 == 1).nice_unwrap_panic();
+                actual.poke(input, seed & 1 == 1).nice_unwrap_panic();
+            }
+            expected.settle().nice_unwrap_panic();
+            actual.settle().nice_unwrap_panic();
+
+            for signal in expected.signals() {
+                assert_eq!(
+                    expected.peek(signal).nice_unwrap_panic(),
+                    actual.peek(signal).nice_unwrap_panic(),
+                    "{} differs",
+                    signal
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn not_levelisable() {
+        let src = "
+        circuit inv: a -> o {
+            x = not(a);
+            o = x;
+        }
+
+        test main {
+            at 0ns:
+                i = 0;
+                o = inv(i);
+                i = inv.x;
+        }
+        ";
+
+        match simulator(src, Engine::Compiled)
+            .run_test("main")
+            .and_then(TestReport::into_result)
+        {
+            Err(SimulationError::NotLevelisable(e)) => assert_eq!(
+                e.to_string(),
+                "the compiled engine can't simulate a loop without delay through \
+                main.i, main.inv.a, main.inv.o, main.inv.x, main.o"
+            ),
+            Err(e) => panic!("expected a loop, got {:?}", e),
+            Ok(()) => panic!("expected a loop"),
+        }
+    }
+}
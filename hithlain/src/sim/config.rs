@@ -1,9 +1,11 @@
+use crate::sim::observer::ObserverFactory;
 use crate::time::Duration;
 use crate::vcd::VcdError;
 use miette::Diagnostic;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug, Diagnostic)]
@@ -16,24 +18,68 @@ pub struct FileNotFound {
 pub enum VcdPath {
     InMemory,
     Path(PathBuf),
+    /// one file per simulated test or check, named after it
+    Directory(PathBuf),
 }
 
 impl VcdPath {
-    pub fn writer(&self) -> Result<Box<dyn Write>, VcdError> {
-        Ok(match self {
-            VcdPath::InMemory => Box::new(Vec::<u8>::new()),
-            VcdPath::Path(p) => {
-                Box::new(File::create(&p).map_err(|_| FileNotFound { name: p.clone() })?)
-            }
-        })
+    pub fn writer(&self, name: &str) -> Result<Box<dyn Write>, VcdError> {
+        let create = |p: PathBuf| -> Result<Box<dyn Write>, VcdError> {
+            Ok(Box::new(
+                File::create(&p).map_err(|_| FileNotFound { name: p.clone() })?,
+            ))
+        };
+
+        match self {
+            VcdPath::InMemory => Ok(Box::new(Vec::<u8>::new())),
+            VcdPath::Path(p) => create(p.clone()),
+            VcdPath::Directory(d) => create(d.join(format!("{}.vcd", name))),
+        }
     }
 }
 
+/// How delayed statements treat changes that are shorter than their delay.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DelayMode {
+    /// pulses shorter than the delay are swallowed, like a real gate would
+    #[default]
+    Inertial,
+    /// every change arrives, just later
+    Transport,
+}
+
+/// How combinational logic is simulated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// every statement runs as an event, one delta after its inputs change
+    #[default]
+    EventDriven,
+    /// statements without delay are compiled into a levelised netlist,
+    /// that is evaluated in one pass after its inputs change
+    Compiled,
+}
+
 pub struct SimulationConfig {
     pub create_vcd: bool,
     pub vcd_path: VcdPath,
     pub vcd_overshoot_duration: Duration,
     pub simulation_time: Option<Duration>,
+    /// stop at the first failing assertion (or test) instead of collecting all failures
+    pub fail_fast: bool,
+    /// the number of delta cycles after which a time step is considered to oscillate
+    pub max_deltas: u64,
+    pub delay_mode: DelayMode,
+    pub engine: Engine,
+    /// run events at the same time in a random order, generated from this seed,
+    /// instead of in the order they were scheduled in
+    pub shuffle_seed: Option<u64>,
+    /// run every test a second time with events at the same time in reverse order, and fail
+    /// when that changes the outcome
+    pub detect_races: bool,
+    /// how many tests are simulated at the same time
+    pub jobs: usize,
+    /// every simulation gets an observer from each of these, besides the vcd writer
+    pub observers: Vec<Arc<dyn ObserverFactory>>,
 }
 
 impl Default for SimulationConfig {
@@ -43,6 +89,14 @@ impl Default for SimulationConfig {
             vcd_path: VcdPath::InMemory,
             vcd_overshoot_duration: Duration::from_nanos(10),
             simulation_time: None,
+            fail_fast: false,
+            max_deltas: 1000,
+            delay_mode: DelayMode::default(),
+            engine: Engine::default(),
+            shuffle_seed: None,
+            detect_races: false,
+            jobs: 1,
+            observers: Vec::new(),
         }
     }
 }
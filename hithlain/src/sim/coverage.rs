@@ -0,0 +1,781 @@
+use crate::parse::span::{Span, SpanKey};
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::observer::{ObserverFactory, SimulationObserver};
+use crate::sim::value::Value;
+use crate::sim::SimulationError;
+use crate::time::Instant;
+use miette::{Diagnostic, LabeledSpan, NamedSource, Severity, SourceCode};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use thiserror::Error;
+
+/// Whether a single signal of a single instance rose and fell, in any of the tests.
+#[derive(Debug, Clone)]
+pub struct SignalCoverage {
+    /// the instance path without the test for signals in circuits, the full name otherwise
+    pub(crate) name: String,
+    pub(crate) instance: usize,
+    /// where the signal is defined
+    pub(crate) span: Option<Span>,
+    pub(crate) rose: bool,
+    pub(crate) fell: bool,
+}
+
+impl SignalCoverage {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Which of the instances with the same name, starting at 0.
+    #[must_use]
+    pub fn instance(&self) -> usize {
+        self.instance
+    }
+
+    #[must_use]
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    #[must_use]
+    pub fn rose(&self) -> bool {
+        self.rose
+    }
+
+    #[must_use]
+    pub fn fell(&self) -> bool {
+        self.fell
+    }
+
+    /// Whether the signal both rose and fell.
+    #[must_use]
+    pub fn toggled(&self) -> bool {
+        self.rose && self.fell
+    }
+}
+
+impl Display for SignalCoverage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = match (self.rose, self.fell) {
+            (true, true) => "ok",
+            (true, false) => "never fell",
+            (false, true) => "never rose",
+            (false, false) => "never toggled",
+        };
+
+        write!(f, "{}", self.name)?;
+        if self.instance > 0 {
+            write!(f, " (instance {})", self.instance + 1)?;
+        }
+        write!(f, " ... {}", status)
+    }
+}
+
+/// How many signals toggled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CoverageSummary {
+    pub signals: usize,
+    pub toggled: usize,
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn percentage(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+impl Display for CoverageSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "toggle coverage: {} of {} signals rose and fell ({:.1}%)",
+            self.toggled,
+            self.signals,
+            percentage(self.toggled, self.signals)
+        )
+    }
+}
+
+/// Records which signals rose and fell, in every simulation it is registered for in the
+/// [`SimulationConfig`](crate::sim::config::SimulationConfig). Clones share their results,
+/// so keep one to read them once the tests ran.
+#[derive(Debug, Clone, Default)]
+pub struct ToggleCoverage {
+    /// by name, and the instance to tell instances of the same circuit apart
+    signals: Arc<Mutex<BTreeMap<(String, usize), SignalCoverage>>>,
+}
+
+impl ToggleCoverage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<(String, usize), SignalCoverage>> {
+        // a test that panicked can't have left the map half updated
+        self.signals.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Every signal of every simulation so far, sorted by name.
+    #[must_use]
+    pub fn signals(&self) -> Vec<SignalCoverage> {
+        self.lock().values().cloned().collect()
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> CoverageSummary {
+        let signals = self.lock();
+
+        CoverageSummary {
+            signals: signals.len(),
+            toggled: signals.values().filter(|i| i.toggled()).count(),
+        }
+    }
+
+    /// Writes the coverage in the LCOV format. Every signal definition is a line with a branch
+    /// for rising and one for falling, counting the instances that did. The line itself counts
+    /// the instances in which the signal toggled.
+    pub fn write_lcov(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        // file -> line -> signal -> instances that rose, fell and toggled
+        let mut files: BTreeMap<String, BTreeMap<usize, BTreeMap<String, [usize; 3]>>> =
+            BTreeMap::new();
+
+        for i in self.lock().values() {
+            let span = match &i.span {
+                Some(span) => span,
+                None => continue,
+            };
+            let variable = i.name.rsplit('.').next().unwrap_or(&i.name);
+
+            let counts = files
+                .entry(span.source().name().to_string())
+                .or_default()
+                .entry(span.line())
+                .or_default()
+                .entry(variable.to_string())
+                .or_default();
+            counts[0] += usize::from(i.rose);
+            counts[1] += usize::from(i.fell);
+            counts[2] += usize::from(i.toggled());
+        }
+
+        writeln!(w, "TN:")?;
+        for (file, lines) in files {
+            writeln!(w, "SF:{}", file)?;
+
+            let (mut branches, mut branches_hit) = (0, 0);
+            for (line, signals) in &lines {
+                for (block, [rose, fell, _]) in signals.values().enumerate() {
+                    writeln!(w, "BRDA:{},{},0,{}", line, block, rose)?;
+                    writeln!(w, "BRDA:{},{},1,{}", line, block, fell)?;
+                    branches += 2;
+                    branches_hit += usize::from(*rose > 0) + usize::from(*fell > 0);
+                }
+            }
+            writeln!(w, "BRF:{}", branches)?;
+            writeln!(w, "BRH:{}", branches_hit)?;
+
+            let mut lines_hit = 0;
+            for (line, signals) in &lines {
+                let toggled: usize = signals.values().map(|i| i[2]).sum();
+                writeln!(w, "DA:{},{}", line, toggled)?;
+                lines_hit += usize::from(toggled > 0);
+            }
+            writeln!(w, "LF:{}", lines.len())?;
+            writeln!(w, "LH:{}", lines_hit)?;
+
+            writeln!(w, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ObserverFactory for ToggleCoverage {
+    fn create(&self, _name: &str) -> Box<dyn SimulationObserver> {
+        Box::new(ToggleObserver {
+            coverage: self.clone(),
+            values: HashMap::new(),
+            toggles: HashMap::new(),
+        })
+    }
+}
+
+struct ToggleObserver {
+    coverage: ToggleCoverage,
+    /// the last value of every signal, to know which way it changes
+    values: HashMap<usize, Value>,
+    /// the key of every signal by identifier, and whether it rose and fell in this simulation
+    toggles: HashMap<usize, ((String, usize), bool, bool)>,
+}
+
+impl ToggleObserver {
+    /// Adds what was seen so far to the shared results.
+    fn merge(&mut self) {
+        if self.toggles.is_empty() {
+            return;
+        }
+
+        let mut coverage = self.coverage.lock();
+        for (_, (key, rose, fell)) in self.toggles.drain() {
+            if let Some(i) = coverage.get_mut(&key) {
+                i.rose |= rose;
+                i.fell |= fell;
+            }
+        }
+    }
+}
+
+impl Drop for ToggleObserver {
+    fn drop(&mut self) {
+        // simulations that stop with an error aren't finished
+        self.merge();
+    }
+}
+
+impl SimulationObserver for ToggleObserver {
+    fn start(&mut self, signals: &[UniqueVariableRef]) {
+        // in the order they were created, so instances are numbered the same in every test
+        let mut signals: Vec<_> = signals.iter().filter(|i| !i.generated).collect();
+        signals.sort_by_key(|i| i.identifier);
+
+        let mut coverage = self.coverage.lock();
+        let mut instances = HashMap::new();
+        for i in signals {
+            // signals in circuits are combined over all tests that use the circuit
+            let name = if i.in_circuit() {
+                i.instance_path()
+            } else {
+                i.name()
+            };
+            let instance = instances.entry(name.clone()).or_insert(0);
+            *instance += 1;
+            let key = (name, *instance - 1);
+
+            coverage
+                .entry(key.clone())
+                .or_insert_with(|| SignalCoverage {
+                    name: key.0.clone(),
+                    instance: key.1,
+                    span: i.original.variable.1.clone(),
+                    rose: false,
+                    fell: false,
+                });
+            self.toggles.insert(i.identifier, (key, false, false));
+        }
+    }
+
+    fn value_changed(
+        &mut self,
+        variable: &UniqueVariableRef,
+        value: &Value,
+        _time: Instant,
+    ) -> Result<(), SimulationError> {
+        if variable.generated {
+            return Ok(());
+        }
+
+        let Value::Bit(after) = *value;
+        if let Some(Value::Bit(before)) = self.values.insert(variable.identifier, value.clone()) {
+            if before != after {
+                if let Some((_, rose, fell)) = self.toggles.get_mut(&variable.identifier) {
+                    *rose |= after;
+                    *fell |= !after;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, _time: Instant) -> Result<(), SimulationError> {
+        self.merge();
+        Ok(())
+    }
+}
+
+/// Whether an expression or assignment in a circuit evaluated to 0 and to 1, in any instance.
+#[derive(Debug, Clone)]
+pub struct ExpressionResults {
+    pub(crate) span: Span,
+    pub(crate) zero: bool,
+    pub(crate) one: bool,
+}
+
+impl ExpressionResults {
+    #[must_use]
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    #[must_use]
+    pub fn zero(&self) -> bool {
+        self.zero
+    }
+
+    #[must_use]
+    pub fn one(&self) -> bool {
+        self.one
+    }
+
+    /// Whether the expression evaluated to both 0 and 1.
+    #[must_use]
+    pub fn covered(&self) -> bool {
+        self.zero && self.one
+    }
+}
+
+/// How many expressions evaluated to both 0 and 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ExpressionSummary {
+    pub expressions: usize,
+    pub covered: usize,
+}
+
+impl Display for ExpressionSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expression coverage: {} of {} expressions were both 0 and 1 ({:.1}%)",
+            self.covered,
+            self.expressions,
+            percentage(self.covered, self.expressions)
+        )
+    }
+}
+
+/// The expressions in one source file that never evaluated to both 0 and 1, as a
+/// diagnostic that shows them in the source.
+#[derive(Debug, Error)]
+#[error("{} expression(s) in {} never evaluated to both 0 and 1", labels.len(), file)]
+pub struct UncoveredExpressions {
+    file: String,
+    src: NamedSource,
+    labels: Vec<LabeledSpan>,
+}
+
+impl Diagnostic for UncoveredExpressions {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Advice)
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.src)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(self.labels.iter().cloned()))
+    }
+}
+
+/// Records which builtin gate expressions in circuits evaluated to 0 and 1, in every simulation
+/// it is registered for. Desugared temporaries remember the expression they hold, so every
+/// part of a nested expression is counted on its own. Plain assignments and instance calls
+/// are counted at every variable they assign. Clones share their results.
+#[derive(Debug, Clone, Default)]
+pub struct ExpressionCoverage {
+    /// by file and position in it
+    expressions: Arc<Mutex<BTreeMap<SpanKey, ExpressionResults>>>,
+}
+
+impl ExpressionCoverage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<SpanKey, ExpressionResults>> {
+        self.expressions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Every expression of every circuit that was simulated so far, in source order.
+    #[must_use]
+    pub fn expressions(&self) -> Vec<ExpressionResults> {
+        self.lock().values().cloned().collect()
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> ExpressionSummary {
+        let expressions = self.lock();
+
+        ExpressionSummary {
+            expressions: expressions.len(),
+            covered: expressions.values().filter(|i| i.covered()).count(),
+        }
+    }
+
+    /// The expressions that weren't covered, for every file that has any.
+    #[must_use]
+    pub fn uncovered(&self) -> Vec<UncoveredExpressions> {
+        let mut res: Vec<UncoveredExpressions> = Vec::new();
+
+        for i in self.lock().values().filter(|i| !i.covered()) {
+            let source = i.span.source();
+            let label = match (i.zero, i.one) {
+                (true, _) => "never 1",
+                (_, true) => "never 0",
+                _ => "never evaluated",
+            };
+            let label = LabeledSpan::new_with_span(Some(label.to_string()), i.span.clone());
+
+            match res.last_mut() {
+                Some(last) if last.file == source.name() => last.labels.push(label),
+                _ => res.push(UncoveredExpressions {
+                    file: source.name().to_string(),
+                    src: source.clone().into(),
+                    labels: vec![label],
+                }),
+            }
+        }
+
+        res
+    }
+}
+
+impl ObserverFactory for ExpressionCoverage {
+    fn create(&self, _name: &str) -> Box<dyn SimulationObserver> {
+        Box::new(ExpressionObserver {
+            coverage: self.clone(),
+            expressions: HashMap::new(),
+        })
+    }
+}
+
+struct ExpressionObserver {
+    coverage: ExpressionCoverage,
+    /// the expression or assignment a variable holds the result of, by identifier
+    expressions: HashMap<usize, SpanKey>,
+}
+
+impl SimulationObserver for ExpressionObserver {
+    fn start(&mut self, signals: &[UniqueVariableRef]) {
+        let mut coverage = self.coverage.lock();
+
+        for i in signals {
+            let span = match &i.driver {
+                Some(driver) if i.in_circuit() => driver.span(),
+                _ => continue,
+            };
+
+            let key = span.key();
+            coverage
+                .entry(key.clone())
+                .or_insert_with(|| ExpressionResults {
+                    span: span.clone(),
+                    zero: false,
+                    one: false,
+                });
+            self.expressions.insert(i.identifier, key);
+        }
+    }
+
+    fn value_changed(
+        &mut self,
+        variable: &UniqueVariableRef,
+        value: &Value,
+        _time: Instant,
+    ) -> Result<(), SimulationError> {
+        if let Some(key) = self.expressions.get(&variable.identifier) {
+            if let Some(i) = self.coverage.lock().get_mut(key) {
+                let Value::Bit(b) = *value;
+                i.one |= b;
+                i.zero |= !b;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpressionCoverage, ToggleCoverage};
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::sim::config::SimulationConfig;
+    use crate::sim::Simulator;
+    use miette::Diagnostic;
+    use std::sync::Arc;
+
+    #[test]
+    fn toggles() {
+        let src = "
+circuit inv: a -> o {
+    o = not(a);
+}
+
+test once {
+    o = inv(a);
+
+    at 0ns:
+        a = 0;
+    at 5ns:
+        a = 1;
+}
+
+test twice {
+    o = inv(a);
+
+    at 0ns:
+        a = 1;
+    at 5ns:
+        a = 0;
+    at 10ns:
+        a = 1;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let coverage = ToggleCoverage::new();
+        let config = SimulationConfig {
+            observers: vec![Arc::new(coverage.clone())],
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        let signals: Vec<_> = coverage.signals().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            signals,
+            vec![
+                "inv.a ... ok",
+                "inv.o ... ok",
+                "once.a ... never fell",
+                "once.o ... never rose",
+                "twice.a ... ok",
+                "twice.o ... ok",
+            ]
+        );
+        assert_eq!(
+            coverage.summary().to_string(),
+            "toggle coverage: 4 of 6 signals rose and fell (66.7%)"
+        );
+
+        let mut lcov = Vec::new();
+        coverage.write_lcov(&mut lcov).unwrap();
+        assert_eq!(
+            String::from_utf8(lcov).unwrap(),
+            "TN:\nSF:test\n\
+            BRDA:2,0,0,1\nBRDA:2,0,1,1\nBRDA:2,1,0,1\nBRDA:2,1,1,1\n\
+            BRDA:7,0,0,1\nBRDA:7,0,1,0\nBRDA:7,1,0,0\nBRDA:7,1,1,1\n\
+            BRDA:16,0,0,1\nBRDA:16,0,1,1\nBRDA:16,1,0,1\nBRDA:16,1,1,1\n\
+            BRF:12\nBRH:10\n\
+            DA:2,2\nDA:7,0\nDA:16,2\nLF:3\nLH:2\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn combined_over_tests() {
+        let src = "
+circuit add: a b -> o {
+    o = a xor b;
+}
+
+test one {
+    o = add(a, 0);
+
+    at 0ns:
+        a = 0;
+    at 5ns:
+        a = 1;
+    at 10ns:
+        a = 0;
+}
+
+test two {
+    o = add(0, b);
+    p = add(0, 0);
+
+    at 0ns:
+        b = 1;
+    at 5ns:
+        b = 0;
+    at 10ns:
+        b = 1;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let coverage = ToggleCoverage::new();
+        let config = SimulationConfig {
+            observers: vec![Arc::new(coverage.clone())],
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        let signals: Vec<_> = coverage
+            .signals()
+            .iter()
+            .filter(|i| i.name().starts_with("add."))
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            signals,
+            vec![
+                "add.a ... ok",
+                "add.a (instance 2) ... never toggled",
+                "add.b ... ok",
+                "add.b (instance 2) ... never toggled",
+                "add.o ... ok",
+                "add.o (instance 2) ... never toggled",
+            ]
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let src = "
+circuit mux: s a b -> o {
+    n = not(s);
+    o = (s and a) or (n and b);
+}
+
+test main {
+    o = mux(s, a, 1);
+
+    at 0ns:
+        s = 1;
+        a = 1;
+    at 5ns:
+        a = 0;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let coverage = ExpressionCoverage::new();
+        let config = SimulationConfig {
+            observers: vec![Arc::new(coverage.clone())],
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        let expressions: Vec<_> = coverage
+            .expressions()
+            .iter()
+            .map(|i| {
+                let span = i.span();
+                (&src[span.start()..span.end()], i.zero(), i.one())
+            })
+            .collect();
+        assert_eq!(
+            expressions,
+            vec![
+                ("not(s)", true, false),
+                ("(s and a) or (n and b)", true, true),
+                ("s and a", true, true),
+                ("n and b", true, false),
+            ]
+        );
+        assert_eq!(
+            coverage.summary().to_string(),
+            "expression coverage: 2 of 4 expressions were both 0 and 1 (50.0%)"
+        );
+
+        let uncovered = coverage.uncovered();
+        assert_eq!(uncovered.len(), 1);
+        assert_eq!(
+            uncovered[0].to_string(),
+            "2 expression(s) in test never evaluated to both 0 and 1"
+        );
+        let labels: Vec<_> = uncovered[0]
+            .labels()
+            .unwrap()
+            .map(|i| i.label().unwrap().to_string())
+            .collect();
+        assert_eq!(labels, vec!["never 1", "never 1"]);
+    }
+
+    #[test]
+    fn assignments() {
+        let src = "
+circuit half_add: a b -> o c {
+    o = a xor b;
+    c = a and b;
+}
+
+circuit add: a b c_in -> o c_out {
+    x, c1 = half_add(a, b);
+    o, c2 = half_add(x, c_in);
+    c_out = c1;
+}
+
+test main {
+    o, c_out = add(a, b, 0);
+
+    at 0ns:
+        a = 0;
+        b = 0;
+    at 5ns:
+        a = 1;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let coverage = ExpressionCoverage::new();
+        let config = SimulationConfig {
+            observers: vec![Arc::new(coverage.clone())],
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        // the instance calls and the move in `add`, but not the call in the test
+        let expressions: Vec<_> = coverage
+            .expressions()
+            .iter()
+            .filter(|i| i.span().line() >= 7)
+            .map(|i| {
+                let span = i.span();
+                (
+                    span.line(),
+                    &src[span.start()..span.end()],
+                    i.zero(),
+                    i.one(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            expressions,
+            vec![
+                (8, "x", true, true),
+                (8, "c1", true, false),
+                (9, "o", true, true),
+                (9, "c2", true, false),
+                (10, "c_out", true, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::parse::ast::Constant;
+use crate::parse::desugared_ast::Process;
+use crate::sim::coverage::percentage;
+use crate::sim::instantiate::{instantiate_program, UniqueVariableRef};
+use crate::sim::link::link_process;
+use crate::sim::linked_ast as l;
+use crate::sim::report::{TestReport, TestStatus};
+use crate::sim::{parallel_map, Rerun, Simulator};
+use crate::time::Instant;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// Where a fault is injected, by instance path without the test, like `add2.add.c_out`.
+/// Instances of the same circuit share names, so they are numbered in the order they are created.
+type Site = (String, usize);
+
+/// A signal that keeps the same value for the whole simulation, whatever drives it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fault {
+    pub(crate) signal: String,
+    pub(crate) instance: usize,
+    pub(crate) stuck_at: bool,
+}
+
+impl Fault {
+    /// The instance path of the signal, without the test.
+    #[must_use]
+    pub fn signal(&self) -> &str {
+        &self.signal
+    }
+
+    /// Which of the instances with the same path, starting at 0.
+    #[must_use]
+    pub fn instance(&self) -> usize {
+        self.instance
+    }
+
+    #[must_use]
+    pub fn stuck_at(&self) -> bool {
+        self.stuck_at
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.signal)?;
+        if self.instance > 0 {
+            write!(f, " (instance {})", self.instance + 1)?;
+        }
+        write!(f, " stuck-at-{}", u8::from(self.stuck_at))
+    }
+}
+
+/// The signals in a linked test that faults can be injected on: every signal
+/// in a circuit that isn't generated.
+fn fault_sites(process: &l::Process) -> BTreeMap<Site, UniqueVariableRef> {
+    // in the order they were created, so instances are numbered the same in every test
+    let mut signals = BTreeMap::new();
+    for i in &process.conditions {
+        let written = match i {
+            l::Condition::AtTime { run, .. } | l::Condition::WhenChanges { run, .. } => {
+                run.writes()
+            }
+            l::Condition::Window { .. } => None,
+        };
+
+        if let Some(v) = written {
+            if v.in_circuit() && !v.generated {
+                signals.insert(v.identifier, v.clone());
+            }
+        }
+    }
+
+    let mut instances = HashMap::new();
+    signals
+        .into_values()
+        .map(|v| {
+            let name = v.instance_path();
+            let instance = instances.entry(name.clone()).or_insert(0);
+            *instance += 1;
+            ((name, *instance - 1), v)
+        })
+        .collect()
+}
+
+/// Forces every faulty signal that exists in the process to its value, from the start.
+pub(crate) fn inject_faults(process: &mut l::Process, faults: &[Fault]) {
+    let sites = fault_sites(process);
+    for i in faults {
+        if let Some(v) = sites.get(&(i.signal.clone(), i.instance)) {
+            process.conditions.insert(
+                0,
+                l::Condition::AtTime {
+                    time: Instant::START,
+                    run: Arc::new(l::Statement::Force(v.clone(), Constant::Bit(i.stuck_at))),
+                },
+            );
+        }
+    }
+}
+
+/// One fault, and whether the tests noticed it.
+#[derive(Debug, Clone)]
+pub struct FaultResult {
+    pub(crate) fault: Fault,
+    /// the first test that failed because of it
+    pub(crate) detected_by: Option<String>,
+}
+
+impl FaultResult {
+    #[must_use]
+    pub fn fault(&self) -> &Fault {
+        &self.fault
+    }
+
+    #[must_use]
+    pub fn detected_by(&self) -> Option<&str> {
+        self.detected_by.as_deref()
+    }
+}
+
+/// The outcome of a fault simulation.
+#[derive(Debug, Clone)]
+pub struct FaultReport {
+    pub(crate) results: Vec<FaultResult>,
+    /// the tests that looked for faults
+    pub(crate) tests: Vec<String>,
+    /// tests that already fail without faults, and can't detect any
+    pub(crate) failing: Vec<String>,
+}
+
+impl FaultReport {
+    /// Every fault, ordered by signal.
+    #[must_use]
+    pub fn results(&self) -> &[FaultResult] {
+        &self.results
+    }
+
+    #[must_use]
+    pub fn tests(&self) -> &[String] {
+        &self.tests
+    }
+
+    #[must_use]
+    pub fn failing(&self) -> &[String] {
+        &self.failing
+    }
+
+    pub fn detected(&self) -> impl Iterator<Item = &FaultResult> {
+        self.results.iter().filter(|i| i.detected_by.is_some())
+    }
+
+    pub fn undetected(&self) -> impl Iterator<Item = &FaultResult> {
+        self.results.iter().filter(|i| i.detected_by.is_none())
+    }
+
+    /// The percentage of faults that was detected.
+    #[must_use]
+    pub fn coverage(&self) -> f64 {
+        percentage(self.detected().count(), self.results.len())
+    }
+}
+
+impl Display for FaultReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.failing.is_empty() {
+            writeln!(
+                f,
+                "these tests fail without faults, so they weren't used: {}",
+                self.failing.join(", ")
+            )?;
+        }
+
+        let mut undetected = self.undetected().peekable();
+        if undetected.peek().is_some() {
+            writeln!(f, "undetected faults:")?;
+            for i in undetected {
+                writeln!(f, "  {}", i.fault)?;
+            }
+        }
+
+        write!(
+            f,
+            "fault coverage: {} of {} faults detected by {} test(s) ({:.1}%)",
+            self.detected().count(),
+            self.results.len(),
+            self.tests.len(),
+            self.coverage()
+        )
+    }
+}
+
+impl Simulator {
+    /// Injects stuck-at-0 and stuck-at-1 faults on every signal in a circuit, one at a time,
+    /// and runs the tests again for each of them. A fault is detected when any test fails
+    /// because of it. Only the tests in `reports` that passed are used, leaving out tests that
+    /// should fail. Checks in `reports` are left out too.
+    #[must_use]
+    pub fn fault_simulation(&self, reports: &[TestReport]) -> FaultReport {
+        let mut tests = Vec::new();
+        let mut failing = Vec::new();
+        for report in reports {
+            let test = match self.program.tests.iter().find(|i| i.name.0 == report.name) {
+                Some(test) if !test.attributes.should_fail => test,
+                _ => continue,
+            };
+
+            if report.status == TestStatus::Failed {
+                failing.push(test.name.0.clone());
+            } else if report.status == TestStatus::Passed {
+                let sites: BTreeSet<_> = fault_sites(&link_process(instantiate_program(test)))
+                    .into_keys()
+                    .collect();
+                tests.push((test, sites));
+            }
+        }
+
+        let sites: BTreeSet<_> = tests.iter().flat_map(|(_, sites)| sites).cloned().collect();
+        let faults: Vec<_> = sites
+            .into_iter()
+            .flat_map(|(signal, instance)| {
+                [false, true].map(|stuck_at| Fault {
+                    signal: signal.clone(),
+                    instance,
+                    stuck_at,
+                })
+            })
+            .collect();
+
+        let results = parallel_map(
+            self.config.jobs,
+            &faults,
+            |_| false,
+            |fault| FaultResult {
+                fault: fault.clone(),
+                detected_by: tests
+                    .iter()
+                    .filter(|(_, sites)| sites.contains(&(fault.signal.clone(), fault.instance)))
+                    .find(|(test, _)| !self.passes(test, std::slice::from_ref(fault)))
+                    .map(|(test, _)| test.name.0.clone()),
+            },
+        );
+
+        FaultReport {
+            results,
+            tests: tests.iter().map(|(i, _)| i.name.0.clone()).collect(),
+            failing,
+        }
+    }
+
+    fn passes(&self, test: &Arc<Process>, faults: &[Fault]) -> bool {
+        let mut report = TestReport::new(&test.name.0);
+        self.execute_process(test, &mut report, Some(Rerun::Faulty(faults)), None)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::sim::config::SimulationConfig;
+    use crate::sim::Simulator;
+
+    #[test]
+    fn full_adder() {
+        let src = "
+circuit add: a b c_in -> o c_out {
+    o = a xor b xor c_in;
+    c_out = (a and b) or ((a xor b) and c_in);
+}
+
+test weak {
+    o, c_out = add(a, b, c_in);
+
+    at 0ns:
+        a = 1;
+        b = 1;
+        c_in = 0;
+    at 1ns:
+        assert o == 0;
+        assert c_out == 1;
+}
+
+test broken {
+    o, c_out = add(a, b, c_in);
+
+    at 0ns:
+        a = 0;
+        b = 0;
+        c_in = 0;
+    at 1ns:
+        assert o == 1;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+        let program =
+            desugar_program(&parser.parse_program().nice_unwrap_panic()).nice_unwrap_panic();
+        let s = Simulator::new(program, SimulationConfig::default()).nice_unwrap_panic();
+
+        let report = s.fault_simulation(&s.run_tests(&[]));
+        assert_eq!(report.tests(), ["weak"]);
+        assert_eq!(report.failing(), ["broken"]);
+        // a b c_in o c_out, each stuck at 0 and 1
+        assert_eq!(report.results().len(), 10);
+
+        let undetected: Vec<_> = report.undetected().map(|i| i.fault().to_string()).collect();
+        assert_eq!(
+            undetected,
+            vec![
+                "add.a stuck-at-1",
+                "add.b stuck-at-1",
+                "add.c_in stuck-at-0",
+                "add.c_out stuck-at-1",
+                "add.o stuck-at-0",
+            ]
+        );
+        assert!(report.detected().all(|i| i.detected_by() == Some("weak")));
+        assert_eq!(report.coverage(), 50.0);
+    }
+}
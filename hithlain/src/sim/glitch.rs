@@ -0,0 +1,253 @@
+use crate::parse::span::{Span, SpanKey};
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::observer::{ObserverFactory, SimulationObserver};
+use crate::sim::value::Value;
+use crate::sim::SimulationError;
+use crate::time::Instant;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(
+    "{} glitched in {} time step(s), first at {}ns in {}",
+    what,
+    steps,
+    first,
+    test
+)]
+#[diagnostic(
+    severity = "warning",
+    help("it changed more than once before settling, which means its inputs arrive at different times. this is often a hazard")
+)]
+pub struct Glitch {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("driven here")]
+    span: SourceSpan,
+
+    what: String,
+    steps: usize,
+    first: u64,
+    test: String,
+}
+
+/// Every time step in which the outcome of one expression glitched, in any instance.
+#[derive(Debug, Clone)]
+pub struct Glitches {
+    pub(crate) span: Span,
+    /// the signal it is assigned to, or `None` for part of an expression
+    pub(crate) signal: Option<String>,
+    /// the test, check or circuit in which it first glitched
+    pub(crate) test: String,
+    pub(crate) times: Vec<Instant>,
+}
+
+impl Glitches {
+    #[must_use]
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    #[must_use]
+    pub fn signal(&self) -> Option<&str> {
+        self.signal.as_deref()
+    }
+
+    #[must_use]
+    pub fn test(&self) -> &str {
+        &self.test
+    }
+
+    #[must_use]
+    pub fn times(&self) -> &[Instant] {
+        &self.times
+    }
+
+    /// The glitches as a warning that points at the expression.
+    #[must_use]
+    pub fn warning(&self) -> Glitch {
+        Glitch {
+            src: self.span.source().clone().into(),
+            span: self.span.clone().into(),
+            what: self
+                .signal
+                .clone()
+                .unwrap_or_else(|| "an expression".to_string()),
+            steps: self.times.len(),
+            first: self.times.first().map_or(0, Instant::nanos),
+            test: self.test.clone(),
+        }
+    }
+}
+
+/// Finds signals that change more than once within a single time step, before the step settles.
+/// Only gates in circuits are watched, so a glitch isn't reported again for every signal it is
+/// moved to, or for the assertions in a test that read it. Clones share their results.
+#[derive(Debug, Clone, Default)]
+pub struct GlitchDetector {
+    /// by file and position in it
+    glitches: Arc<Mutex<BTreeMap<SpanKey, Glitches>>>,
+}
+
+impl GlitchDetector {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<SpanKey, Glitches>> {
+        self.glitches.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Every expression that glitched in any simulation so far, in source order.
+    #[must_use]
+    pub fn glitches(&self) -> Vec<Glitches> {
+        self.lock().values().cloned().collect()
+    }
+
+    #[must_use]
+    pub fn warnings(&self) -> Vec<Glitch> {
+        self.lock().values().map(Glitches::warning).collect()
+    }
+}
+
+impl ObserverFactory for GlitchDetector {
+    fn create(&self, name: &str) -> Box<dyn SimulationObserver> {
+        Box::new(GlitchObserver {
+            detector: self.clone(),
+            test: name.to_string(),
+            initialized: HashSet::new(),
+            changes: HashMap::new(),
+        })
+    }
+}
+
+struct GlitchObserver {
+    detector: GlitchDetector,
+    test: String,
+    /// signals that got their first value, which isn't a change yet
+    initialized: HashSet<usize>,
+    /// the time step a signal last changed in, and how often it changed in it
+    changes: HashMap<usize, ((u64, u64), usize)>,
+}
+
+impl SimulationObserver for GlitchObserver {
+    fn value_changed(
+        &mut self,
+        variable: &UniqueVariableRef,
+        _value: &Value,
+        time: Instant,
+    ) -> Result<(), SimulationError> {
+        let span = match variable.gate() {
+            Some(span) if variable.in_circuit() => span,
+            _ => return Ok(()),
+        };
+        if self.initialized.insert(variable.identifier) {
+            return Ok(());
+        }
+
+        let step = (time.nanos(), time.process_steps());
+        let changes = match self.changes.get_mut(&variable.identifier) {
+            Some((last, changes)) if *last == step => {
+                *changes += 1;
+                *changes
+            }
+            _ => {
+                self.changes.insert(variable.identifier, (step, 1));
+                1
+            }
+        };
+
+        // later changes in the same step are part of the same glitch
+        if changes == 2 {
+            let key = span.key();
+            self.detector
+                .lock()
+                .entry(key)
+                .or_insert_with(|| Glitches {
+                    span: span.clone(),
+                    signal: (!variable.generated).then(|| variable.name()),
+                    test: self.test.clone(),
+                    times: Vec::new(),
+                })
+                .times
+                .push(time);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlitchDetector;
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::sim::config::{Engine, SimulationConfig};
+    use crate::sim::Simulator;
+    use std::sync::Arc;
+
+    #[test]
+    fn static_hazard() {
+        let src = "
+circuit add: a b c_in -> o c_out {
+    o = a xor b xor c_in;
+    c_out = (a and b) or ((a xor b) and c_in);
+}
+
+test main {
+    o, c_out = add(a, b, c_in);
+
+    at 0ns:
+        a = 1;
+        b = 1;
+        c_in = 1;
+    at 1ns:
+        b = 0;
+        assert c_out == 1;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        for engine in [Engine::EventDriven, Engine::Compiled] {
+            let detector = GlitchDetector::new();
+            let config = SimulationConfig {
+                engine,
+                observers: vec![Arc::new(detector.clone())],
+                ..SimulationConfig::default()
+            };
+            let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+                .nice_unwrap_panic();
+            s.run_test("main")
+                .nice_unwrap_panic()
+                .into_result()
+                .nice_unwrap_panic();
+
+            let glitches = detector.glitches();
+            if engine == Engine::Compiled {
+                assert!(glitches.is_empty());
+                continue;
+            }
+
+            // the carry briefly drops while `(a xor b) and c_in` catches up with `a and b`
+            assert_eq!(glitches.len(), 1);
+            assert_eq!(glitches[0].signal(), Some("main.add.c_out"));
+            assert_eq!(
+                glitches[0].span().text(),
+                "(a and b) or ((a xor b) and c_in)"
+            );
+            assert_eq!(glitches[0].test(), "main");
+            let times: Vec<_> = glitches[0].times().iter().map(|i| i.nanos()).collect();
+            assert_eq!(times, vec![1]);
+        }
+    }
+}
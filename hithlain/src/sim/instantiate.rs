@@ -1,10 +1,12 @@
+use crate::parse::ast::HierarchicalPath;
 use crate::parse::desugared_ast as d;
 use crate::parse::scope::{VariableRef, VariableType};
+use crate::parse::span::Span;
 use crate::sim::instantiated_ast as inst;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::parse::desugared_ast::Statement;
+use crate::parse::desugared_ast::{Driver, Statement};
 use crate::sim::instantiated_ast::{LocalizedVariable, Package};
 use derivative::Derivative;
 use std::fmt::{Debug, Formatter};
@@ -22,6 +24,11 @@ pub struct UniqueVariableRef {
     #[derivative(Hash = "ignore")]
     #[derivative(PartialEq = "ignore")]
     pub(crate) original: LocalizedVariable,
+
+    /// where in the source this variable is computed, if it is
+    #[derivative(Hash = "ignore")]
+    #[derivative(PartialEq = "ignore")]
+    pub(crate) driver: Option<Driver>,
 }
 
 impl UniqueVariableRef {
@@ -37,11 +44,44 @@ impl UniqueVariableRef {
 
         res
     }
+
+    /// The name without the test it is in, like `add2.add.c_out`. Instances of the
+    /// same circuit share it.
+    #[must_use]
+    pub fn instance_path(&self) -> String {
+        let mut res = String::new();
+        for i in self.original.path.iter().skip(1) {
+            res.push_str(&i.name().0);
+            res.push('.');
+        }
+
+        res.push_str(&self.original.variable.0);
+
+        res
+    }
+
+    /// The builtin gate expression that computes this variable, if any.
+    #[must_use]
+    pub fn gate(&self) -> Option<&Span> {
+        match &self.driver {
+            Some(Driver::Gate(span)) => Some(span),
+            _ => None,
+        }
+    }
+
+    /// Whether the variable is in a circuit, instead of directly in a test.
+    #[must_use]
+    pub fn in_circuit(&self) -> bool {
+        self.original
+            .path
+            .iter()
+            .any(|p| matches!(p, Package::Circuit(_)))
+    }
 }
 
 impl Debug for UniqueVariableRef {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for i in &*self.original.path.deref() {
+        for i in self.original.path.iter() {
             write!(f, "{}.", i.name().0)?;
         }
 
@@ -72,6 +112,7 @@ impl UniqueVariableRefGenerator {
             identifier: self.cur,
             generated: false,
             original: variable,
+            driver: None,
         };
         self.cur += 1;
 
@@ -79,12 +120,12 @@ impl UniqueVariableRefGenerator {
     }
 }
 
-#[allow(clippy::option_if_let_else)]
+#[allow(clippy::option_if_let_else, clippy::mutable_key_type)]
 pub fn rename(
     a: &VariableRef,
     mapping: &mut HashMap<VariableRef, UniqueVariableRef>,
     gen: &mut UniqueVariableRefGenerator,
-    package_path: &Rc<Vec<Package>>,
+    package_path: &Arc<Vec<Package>>,
 ) -> UniqueVariableRef {
     if let Some(i) = mapping.get(a) {
         i.clone()
@@ -99,22 +140,45 @@ pub fn rename(
     }
 }
 
+/// Creates the variables that statements compute before anything else refers to them,
+/// so every copy knows where in the source it is computed. The first driver wins.
+#[allow(clippy::mutable_key_type)]
+fn rename_drivers<'a>(
+    statements: impl IntoIterator<Item = &'a d::Statement>,
+    mapping: &mut HashMap<VariableRef, UniqueVariableRef>,
+    gen: &mut UniqueVariableRefGenerator,
+    package_path: &Arc<Vec<Package>>,
+) {
+    for i in statements {
+        for (variable, driver) in i.drivers() {
+            if !mapping.contains_key(variable) {
+                let mut v = rename(variable, mapping, gen, package_path);
+                v.driver = Some(driver);
+                mapping.insert(variable.clone(), v);
+            }
+        }
+    }
+}
+
 #[must_use]
-pub fn instantiate_program(p: &Rc<d::Process>) -> inst::Process {
+pub fn instantiate_program(p: &Arc<d::Process>) -> inst::Process {
     let mut gen = UniqueVariableRefGenerator::new();
 
     instantiate_process(p, &mut gen, vec![])
 }
 
+#[allow(clippy::mutable_key_type)]
 pub fn instantiate_process(
-    c: &Rc<d::Process>,
+    c: &Arc<d::Process>,
     gen: &mut UniqueVariableRefGenerator,
     mut package_path: Vec<Package>,
 ) -> inst::Process {
     package_path.push(c.clone().into());
-    let local_package_path = Rc::new(package_path);
+    let local_package_path = Arc::new(package_path);
 
     let mut mapping = HashMap::new();
+    let statements = c.timed_blocks.iter().flat_map(|i| &i.block);
+    rename_drivers(statements, &mut mapping, gen, &local_package_path);
 
     let inputs = c
         .inputs
@@ -128,23 +192,96 @@ pub fn instantiate_process(
         .map(|i| rename(i, &mut mapping, gen, &local_package_path))
         .collect();
 
+    let mut timed_blocks: Vec<_> = c
+        .timed_blocks
+        .iter()
+        .map(|i| instantiate_timed_block(i, &mut mapping, gen, &local_package_path))
+        .collect();
+
+    // hierarchical references can point into any instance in the process,
+    // so they're resolved once all instances have been created.
+    let resolved: Vec<Vec<_>> = {
+        let instances: Vec<_> = timed_blocks
+            .iter()
+            .flat_map(|i| &i.block)
+            .filter_map(|i| match i {
+                inst::Statement::CreateCircuitInstance(c) => Some(c),
+                _ => None,
+            })
+            .collect();
+
+        c.timed_blocks
+            .iter()
+            .map(|block| {
+                block
+                    .block
+                    .iter()
+                    .filter_map(|i| match i {
+                        Statement::MoveHierarchical(into, path) => Some(inst::Statement::Move(
+                            rename(into, &mut mapping, gen, &local_package_path),
+                            resolve_hierarchical(path, &instances),
+                        )),
+                        Statement::Force(path, value) => Some(inst::Statement::Force(
+                            resolve_hierarchical(path, &instances),
+                            value.clone(),
+                        )),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+
+    for (block, resolved) in timed_blocks.iter_mut().zip(resolved) {
+        block.block.extend(resolved);
+    }
+
     inst::Process {
         name: c.name.clone(),
-        timed_blocks: c
-            .timed_blocks
-            .iter()
-            .map(|i| instantiate_timed_block(i, &mut mapping, gen, &local_package_path))
-            .collect(),
+        timed_blocks,
         inputs,
         outputs,
     }
 }
 
+/// Finds the variable a hierarchical path refers to, starting from the given instances.
+/// Paths are checked during desugaring, so this can't fail.
+fn resolve_hierarchical(
+    path: &HierarchicalPath,
+    instances: &[&inst::Circuit],
+) -> UniqueVariableRef {
+    let mut candidates = instances.to_vec();
+    let mut circuit = None;
+
+    for name in &path.instances {
+        let c: &inst::Circuit = candidates
+            .iter()
+            .find(|c| &c.name == name)
+            .expect("hierarchical paths are checked during desugaring");
+
+        candidates = c
+            .body
+            .iter()
+            .filter_map(|i| match i {
+                inst::Statement::CreateCircuitInstance(c) => Some(c),
+                _ => None,
+            })
+            .collect();
+        circuit = Some(c);
+    }
+
+    circuit
+        .and_then(|c| c.variables.get(&path.variable))
+        .expect("hierarchical paths are checked during desugaring")
+        .clone()
+}
+
+#[allow(clippy::mutable_key_type)]
 pub fn instantiate_timed_block(
     block: &d::TimedBlock,
     mapping: &mut HashMap<VariableRef, UniqueVariableRef>,
     gen: &mut UniqueVariableRefGenerator,
-    package_path: &Rc<Vec<Package>>,
+    package_path: &Arc<Vec<Package>>,
 ) -> inst::TimedBlock {
     inst::TimedBlock {
         time: block.time,
@@ -156,47 +293,57 @@ pub fn instantiate_timed_block(
     }
 }
 
+#[allow(clippy::mutable_key_type)]
 pub fn instantiate_circuit(
-    c: &Rc<d::Circuit>,
+    c: &Arc<d::Circuit>,
     gen: &mut UniqueVariableRefGenerator,
     mut package_path: Vec<Package>,
 ) -> inst::Circuit {
     package_path.push(c.clone().into());
-    let local_package_path = Rc::new(package_path);
+    let local_package_path = Arc::new(package_path);
 
     let mut mapping = HashMap::new();
+    rename_drivers(&c.body, &mut mapping, gen, &local_package_path);
 
     let inputs = c
         .inputs
         .iter()
-        .cloned()
-        .map(|i| rename(&i, &mut mapping, gen, &local_package_path))
+        .map(|i| rename(i, &mut mapping, gen, &local_package_path))
         .collect();
 
     let outputs = c
         .outputs
         .iter()
-        .cloned()
-        .map(|i| rename(&i, &mut mapping, gen, &local_package_path))
+        .map(|i| rename(i, &mut mapping, gen, &local_package_path))
+        .collect();
+
+    let body = c
+        .body
+        .iter()
+        .flat_map(|s| instantiate_statement(s.clone(), &mut mapping, gen, &local_package_path))
+        .collect();
+
+    let variables = mapping
+        .iter()
+        .filter(|(k, _)| k.0.variable_type != VariableType::Temp)
+        .map(|(k, v)| (k.0.variable.clone(), v.clone()))
         .collect();
 
     inst::Circuit {
         name: c.name.clone(),
         inputs,
         outputs,
-        body: c
-            .body
-            .iter()
-            .flat_map(|s| instantiate_statement(s.clone(), &mut mapping, gen, &local_package_path))
-            .collect(),
+        body,
+        variables,
     }
 }
 
+#[allow(clippy::mutable_key_type)]
 pub fn instantiate_statement(
     stmt: d::Statement,
     mapping: &mut HashMap<VariableRef, UniqueVariableRef>,
     gen: &mut UniqueVariableRefGenerator,
-    package_path: &Rc<Vec<Package>>,
+    package_path: &Arc<Vec<Package>>,
 ) -> Vec<inst::Statement> {
     macro_rules! rename_builtin {
         ($($tt:tt)*) => {
@@ -205,6 +352,7 @@ pub fn instantiate_statement(
                     a,
                     b,
                     into,
+                    ..
                 } = $($tt)*;
 
                 inst::BinaryBuiltin {
@@ -217,7 +365,7 @@ pub fn instantiate_statement(
     }
 
     match stmt {
-        d::Statement::Not { input, into } => vec![inst::Statement::Not {
+        d::Statement::Not { input, into, .. } => vec![inst::Statement::Not {
             input: rename(&input, mapping, gen, package_path),
             into: rename(&into, mapping, gen, package_path),
         }],
@@ -231,6 +379,7 @@ pub fn instantiate_statement(
             inputs,
             circuit,
             into,
+            ..
         } => {
             let instantiated_circuit =
                 instantiate_circuit(&circuit, gen, package_path.deref().clone());
@@ -255,7 +404,7 @@ pub fn instantiate_statement(
 
             res
         }
-        d::Statement::Move(a, b) => {
+        d::Statement::Move(a, b, _) => {
             vec![inst::Statement::Move(
                 rename(&a, mapping, gen, package_path),
                 rename(&b, mapping, gen, package_path),
@@ -273,5 +422,24 @@ pub fn instantiate_statement(
                 span,
             )]
         }
+        // resolved in `instantiate_process`, once all instances exist
+        Statement::MoveHierarchical(_, _) | Statement::Force(_, _) => vec![],
+        Statement::TemporalAssert(a, span, temporal) => {
+            vec![inst::Statement::TemporalAssert(
+                rename(&a, mapping, gen, package_path),
+                span,
+                temporal,
+            )]
+        }
+        Statement::Cover(a, bin) => {
+            vec![inst::Statement::Cover(
+                rename(&a, mapping, gen, package_path),
+                bin,
+            )]
+        }
+        Statement::Delayed(s, delay) => instantiate_statement(*s, mapping, gen, package_path)
+            .into_iter()
+            .map(|i| inst::Statement::Delayed(Box::new(i), delay))
+            .collect(),
     }
 }
@@ -1,16 +1,17 @@
-use crate::parse::ast::{Constant, Variable};
+use crate::parse::ast::{Constant, CoverBin, Temporal, Variable};
 use crate::parse::desugared_ast as d;
-use crate::time::Instant;
+use crate::time::{Duration, Instant};
+use std::collections::HashMap;
 
 use crate::parse::span::Span;
 use crate::sim::instantiate::UniqueVariableRef;
 use derive_more::From;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(From, Clone)]
 pub enum Package {
-    Circuit(Rc<d::Circuit>),
-    Test(Rc<d::Process>),
+    Circuit(Arc<d::Circuit>),
+    Test(Arc<d::Process>),
 }
 
 impl Package {
@@ -26,7 +27,7 @@ impl Package {
 #[derive(Clone)]
 pub struct LocalizedVariable {
     pub(crate) variable: Variable,
-    pub(crate) path: Rc<Vec<Package>>,
+    pub(crate) path: Arc<Vec<Package>>,
 }
 
 #[derive(Clone)]
@@ -39,6 +40,8 @@ pub struct BinaryBuiltin {
 #[derive(Clone)]
 pub enum Statement {
     Assert(UniqueVariableRef, Span),
+    TemporalAssert(UniqueVariableRef, Span, Temporal),
+    Cover(UniqueVariableRef, CoverBin),
     Not {
         input: UniqueVariableRef,
         into: UniqueVariableRef,
@@ -51,9 +54,44 @@ pub enum Statement {
     Xnor(BinaryBuiltin),
     Move(UniqueVariableRef, UniqueVariableRef),
     Set(UniqueVariableRef, Constant),
+    Force(UniqueVariableRef, Constant),
+    Delayed(Box<Statement>, Duration),
     CreateCircuitInstance(Circuit),
 }
 
+impl Statement {
+    /// Calls `f` for every variable in the statement, and in the instance it creates.
+    pub(crate) fn for_each_variable(&self, f: &mut impl FnMut(&UniqueVariableRef)) {
+        match self {
+            Statement::Assert(v, _)
+            | Statement::TemporalAssert(v, _, _)
+            | Statement::Cover(v, _)
+            | Statement::Set(v, _)
+            | Statement::Force(v, _) => f(v),
+            Statement::Not { input, into } => {
+                f(input);
+                f(into);
+            }
+            Statement::And(a)
+            | Statement::Or(a)
+            | Statement::Nand(a)
+            | Statement::Nor(a)
+            | Statement::Xor(a)
+            | Statement::Xnor(a) => {
+                f(&a.a);
+                f(&a.b);
+                f(&a.into);
+            }
+            Statement::Move(a, b) => {
+                f(a);
+                f(b);
+            }
+            Statement::Delayed(s, _) => s.for_each_variable(f),
+            Statement::CreateCircuitInstance(c) => c.for_each_variable(f),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Circuit {
     pub(crate) name: Variable,
@@ -62,6 +100,21 @@ pub struct Circuit {
     pub(crate) outputs: Vec<UniqueVariableRef>,
 
     pub(crate) body: Vec<Statement>,
+
+    /// all non-generated variables of this instance, by name
+    pub(crate) variables: HashMap<Variable, UniqueVariableRef>,
+}
+
+impl Circuit {
+    /// Calls `f` for every variable in this instance and the instances it contains,
+    /// including generated ones.
+    pub(crate) fn for_each_variable(&self, f: &mut impl FnMut(&UniqueVariableRef)) {
+        self.inputs.iter().for_each(&mut *f);
+        self.outputs.iter().for_each(&mut *f);
+        for i in &self.body {
+            i.for_each_variable(f);
+        }
+    }
 }
 
 pub struct TimedBlock {
@@ -0,0 +1,444 @@
+use crate::parse::ast::Constant;
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::instantiated_ast as inst;
+use crate::sim::linked_ast::Statement;
+use crate::sim::simulation::{Simulation, SimulationState};
+use crate::sim::snapshot::Snapshot;
+use crate::sim::value::Value;
+use crate::sim::SimulationError;
+use crate::time::Instant;
+use miette::Diagnostic;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("no circuit named {}", name)]
+#[diagnostic(help("available circuits: {}", available))]
+pub struct UnknownCircuit {
+    pub(crate) name: String,
+    pub(crate) available: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("no signal named {}", name)]
+#[diagnostic(help(
+    "signals inside instances are named after the instance, like `add.x`. available signals: {}",
+    available
+))]
+pub struct UnknownSignal {
+    name: String,
+    available: String,
+}
+
+/// A circuit that is simulated on its own, driven step by step from Rust
+/// instead of by a test. Signals are referred to by name: inputs, outputs and
+/// intermediate variables by their own name, signals inside instances by a
+/// hierarchical name like `add.x`.
+pub struct Interactive<'config> {
+    simulation: Simulation<'config>,
+    signals: HashMap<String, UniqueVariableRef>,
+    /// every variable in the circuit by identifier, including generated ones, for snapshots
+    variables: HashMap<usize, UniqueVariableRef>,
+    now: Instant,
+}
+
+/// Names every non-generated variable in a circuit and the instances it contains.
+/// Like hierarchical paths in tests, instances are named after their circuit,
+/// and the first instance of a circuit wins.
+fn name_signals(
+    circuit: &inst::Circuit,
+    prefix: &str,
+    signals: &mut HashMap<String, UniqueVariableRef>,
+) {
+    for (name, variable) in &circuit.variables {
+        signals
+            .entry(format!("{}{}", prefix, name.0))
+            .or_insert_with(|| variable.clone());
+    }
+
+    for i in &circuit.body {
+        if let inst::Statement::CreateCircuitInstance(c) = i {
+            name_signals(c, &format!("{}{}.", prefix, c.name.0), signals);
+        }
+    }
+}
+
+impl<'config> Interactive<'config> {
+    pub(crate) fn new(simulation: Simulation<'config>, circuit: &inst::Circuit) -> Self {
+        let mut signals = HashMap::new();
+        name_signals(circuit, "", &mut signals);
+
+        let mut variables = HashMap::new();
+        circuit.for_each_variable(&mut |v| {
+            variables.insert(v.identifier, v.clone());
+        });
+
+        Self {
+            simulation,
+            signals,
+            variables,
+            now: Instant::START,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<&UniqueVariableRef, SimulationError> {
+        self.signals.get(name).ok_or_else(|| {
+            let mut available: Vec<_> = self.signals.keys().map(String::as_str).collect();
+            available.sort_unstable();
+
+            UnknownSignal {
+                name: name.to_string(),
+                available: available.join(", "),
+            }
+            .into()
+        })
+    }
+
+    /// The names of all signals that can be poked and peeked.
+    pub fn signals(&self) -> impl Iterator<Item = &str> {
+        self.signals.keys().map(String::as_str)
+    }
+
+    /// The current simulation time.
+    #[must_use]
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// Sets a signal to a value. The change propagates once the simulation runs.
+    pub fn poke(&mut self, name: &str, value: impl Into<Value>) -> Result<(), SimulationError> {
+        let variable = self.lookup(name)?.clone();
+        let Value::Bit(b) = value.into();
+
+        self.simulation.schedule(
+            self.now.add_process_step(),
+            Statement::Set(variable, Constant::Bit(b)),
+        );
+
+        Ok(())
+    }
+
+    /// The current value of a signal, or `None` if nothing drove it yet.
+    pub fn peek(&self, name: &str) -> Result<Option<Value>, SimulationError> {
+        Ok(self.simulation.get_var(self.lookup(name)?))
+    }
+
+    /// Runs every event up to and including the given time, and advances the current time to it.
+    pub fn run_until(&mut self, time: Instant) -> Result<(), SimulationError> {
+        while let Some(next) = self.simulation.next_instant() {
+            if next.nanos() > time.nanos() {
+                break;
+            }
+
+            if let SimulationState::TimeLimit { .. } = self.simulation.step()? {
+                break;
+            }
+        }
+
+        self.now = self.now.max(self.simulation.last_instant()).max(time);
+        Ok(())
+    }
+
+    /// Runs until no more events are pending, so every signal has its final value.
+    pub fn settle(&mut self) -> Result<(), SimulationError> {
+        while self.simulation.next_instant().is_some() {
+            if let SimulationState::TimeLimit { .. } = self.simulation.step()? {
+                break;
+            }
+        }
+
+        self.now = self.now.max(self.simulation.last_instant());
+        Ok(())
+    }
+
+    /// Captures the current state, to return to it later with [`Interactive::restore`].
+    #[must_use]
+    pub fn snapshot(&mut self) -> Snapshot {
+        let mut snapshot = self.simulation.snapshot();
+        snapshot.time = self.now;
+        snapshot
+    }
+
+    /// Returns to the state of a snapshot, including its time.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.simulation.restore(snapshot);
+        self.now = snapshot.time;
+    }
+
+    /// Writes a snapshot to a file (or anything else), to be loaded with
+    /// [`Interactive::load_snapshot`] by a simulation of the same circuit.
+    pub fn save_snapshot(
+        &self,
+        snapshot: &Snapshot,
+        writer: &mut dyn Write,
+    ) -> Result<(), SimulationError> {
+        let table = self.simulation.snapshot_table(self.variables.clone());
+        Ok(snapshot.write(&table, writer)?)
+    }
+
+    pub fn load_snapshot(&self, reader: &mut dyn BufRead) -> Result<Snapshot, SimulationError> {
+        let table = self.simulation.snapshot_table(self.variables.clone());
+        Ok(Snapshot::read(&table, reader)?)
+    }
+
+    /// Ends the simulation, writing out the vcd file when one is created.
+    pub fn finish(mut self) -> Result<(), SimulationError> {
+        self.simulation.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::sim::config::{SimulationConfig, VcdPath};
+    use crate::sim::value::Value;
+    use crate::sim::{SimulationError, Simulator};
+    use crate::time::Instant;
+
+    #[test]
+    fn poke_and_peek() {
+        let src = "
+        circuit half_add: a b -> o c {
+            o = a xor b;
+            c = a and b;
+        }
+
+        circuit add: a b c_in -> o c_out {
+            x, c1 = half_add(a, b);
+            o, c2 = half_add(x, c_in);
+            c_out = c1 or c2 after 2ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        let mut sim = s.instantiate("add").nice_unwrap_panic();
+
+        assert_eq!(sim.peek("o").nice_unwrap_panic(), None);
+
+        sim.poke("a", true).nice_unwrap_panic();
+        sim.poke("b", true).nice_unwrap_panic();
+        sim.poke("c_in", false).nice_unwrap_panic();
+        sim.run_until(Instant::nanos_from_start(1))
+            .nice_unwrap_panic();
+
+        assert_eq!(sim.peek("o").nice_unwrap_panic(), Some(Value::Bit(false)));
+        assert_eq!(sim.peek("x").nice_unwrap_panic(), Some(Value::Bit(false)));
+        assert_eq!(
+            sim.peek("half_add.c").nice_unwrap_panic(),
+            Some(Value::Bit(true))
+        );
+        // the carry is delayed
+        assert_eq!(sim.peek("c_out").nice_unwrap_panic(), None);
+
+        sim.settle().nice_unwrap_panic();
+        assert_eq!(
+            sim.peek("c_out").nice_unwrap_panic(),
+            Some(Value::Bit(true))
+        );
+        assert_eq!(sim.now().nanos(), 2);
+
+        sim.poke("b", false).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        assert_eq!(sim.peek("o").nice_unwrap_panic(), Some(Value::Bit(true)));
+        assert_eq!(
+            sim.peek("c_out").nice_unwrap_panic(),
+            Some(Value::Bit(false))
+        );
+        assert_eq!(sim.now().nanos(), 4);
+
+        assert!(matches!(
+            sim.peek("y"),
+            Err(SimulationError::UnknownSignal(_))
+        ));
+        assert!(matches!(
+            s.instantiate("sub"),
+            Err(SimulationError::UnknownCircuit(_))
+        ));
+
+        sim.finish().nice_unwrap_panic();
+    }
+
+    #[test]
+    fn snapshots() {
+        let src = "
+        circuit counter_bit: a b enable -> o c {
+            o = a xor b after 1ns;
+            c = a and b after 1ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        let mut sim = s.instantiate("counter_bit").nice_unwrap_panic();
+
+        sim.poke("a", true).nice_unwrap_panic();
+        sim.poke("b", false).nice_unwrap_panic();
+        // not read by anything, but still part of the state
+        sim.poke("enable", true).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+
+        // the write of `c` is still pending in the snapshot
+        sim.poke("b", true).nice_unwrap_panic();
+        sim.run_until(Instant::nanos_from_start(1))
+            .nice_unwrap_panic();
+        let reset = sim.snapshot();
+        assert_eq!(reset.time().nanos(), 1);
+
+        sim.settle().nice_unwrap_panic();
+        assert_eq!(sim.peek("o").nice_unwrap_panic(), Some(Value::Bit(false)));
+        assert_eq!(sim.peek("c").nice_unwrap_panic(), Some(Value::Bit(true)));
+
+        sim.restore(&reset);
+        assert_eq!(sim.now().nanos(), 1);
+        assert_eq!(sim.peek("o").nice_unwrap_panic(), Some(Value::Bit(true)));
+        assert_eq!(sim.peek("c").nice_unwrap_panic(), Some(Value::Bit(false)));
+
+        sim.poke("a", false).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        assert_eq!(sim.peek("o").nice_unwrap_panic(), Some(Value::Bit(true)));
+        assert_eq!(sim.peek("c").nice_unwrap_panic(), Some(Value::Bit(false)));
+
+        // through the on disk form, into a fresh simulation
+        let mut saved = Vec::new();
+        sim.save_snapshot(&reset, &mut saved).nice_unwrap_panic();
+
+        let mut fresh = s.instantiate("counter_bit").nice_unwrap_panic();
+        let loaded = fresh
+            .load_snapshot(&mut saved.as_slice())
+            .nice_unwrap_panic();
+        fresh.restore(&loaded);
+        fresh.settle().nice_unwrap_panic();
+        assert_eq!(fresh.peek("o").nice_unwrap_panic(), Some(Value::Bit(false)));
+        assert_eq!(fresh.peek("c").nice_unwrap_panic(), Some(Value::Bit(true)));
+        assert_eq!(
+            fresh.peek("enable").nice_unwrap_panic(),
+            Some(Value::Bit(true))
+        );
+
+        let mut resaved = Vec::new();
+        fresh
+            .save_snapshot(&loaded, &mut resaved)
+            .nice_unwrap_panic();
+        assert_eq!(saved, resaved);
+
+        assert!(matches!(
+            fresh.load_snapshot(&mut "not a snapshot".as_bytes()),
+            Err(SimulationError::SnapshotError(_))
+        ));
+    }
+
+    #[test]
+    fn snapshots_in_vcd() {
+        let src = "
+        circuit and_gate: a b -> o {
+            o = a and b after 1ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let path =
+            std::env::temp_dir().join(format!("hithlain-snapshot-{}.vcd", std::process::id()));
+        let config = SimulationConfig {
+            vcd_path: VcdPath::Path(path.clone()),
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugared, config).nice_unwrap_panic();
+        let mut sim = s.instantiate("and_gate").nice_unwrap_panic();
+
+        sim.poke("a", true).nice_unwrap_panic();
+        sim.poke("b", false).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        let reset = sim.snapshot();
+
+        sim.poke("b", true).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        assert_eq!(sim.now().nanos(), 2);
+
+        sim.restore(&reset);
+        sim.poke("a", false).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        sim.finish().nice_unwrap_panic();
+
+        let vcd = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // only what happened after restoring is left, and time never goes back
+        let times: Vec<u64> = vcd
+            .lines()
+            .filter_map(|i| i.strip_prefix('#'))
+            .map(|i| i.parse().unwrap())
+            .collect();
+        assert!(times.windows(2).all(|i| i[0] < i[1]), "{:?}", times);
+        assert_eq!(vcd.lines().filter(|i| i.starts_with('1')).count(), 1);
+    }
+
+    #[test]
+    fn vcd_after_dropping_snapshots() {
+        let src = "
+        circuit and_gate: a b -> o {
+            o = a and b after 1ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let path = std::env::temp_dir().join(format!(
+            "hithlain-dropped-snapshot-{}.vcd",
+            std::process::id()
+        ));
+        let config = SimulationConfig {
+            vcd_path: VcdPath::Path(path.clone()),
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugared, config).nice_unwrap_panic();
+        let mut sim = s.instantiate("and_gate").nice_unwrap_panic();
+
+        sim.poke("a", true).nice_unwrap_panic();
+        sim.poke("b", true).nice_unwrap_panic();
+        let reset = sim.snapshot();
+        sim.settle().nice_unwrap_panic();
+        drop(reset);
+
+        // written as soon as no snapshot can go back anymore, without finishing
+        sim.poke("b", false).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        let vcd = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(vcd.lines().filter(|i| i.starts_with('1')).count(), 3);
+
+        // and whatever is left when the simulation is dropped
+        let reset = sim.snapshot();
+        sim.poke("a", false).nice_unwrap_panic();
+        sim.settle().nice_unwrap_panic();
+        drop(sim);
+        drop(reset);
+
+        let vcd = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vcd.lines().filter(|i| i.starts_with('0')).count(), 3);
+    }
+}
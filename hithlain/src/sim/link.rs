@@ -1,9 +1,10 @@
+use crate::parse::ast::Temporal;
 use crate::sim::instantiated_ast as inst;
 use crate::sim::instantiated_ast::Statement;
 use crate::sim::linked_ast as l;
-use crate::sim::linked_ast::Condition;
+use crate::sim::linked_ast::{Condition, WindowKind};
 use crate::time::Instant;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[must_use]
 pub fn link_statement_list(statements: Vec<Statement>, do_sets: bool) -> Vec<Condition> {
@@ -11,7 +12,7 @@ pub fn link_statement_list(statements: Vec<Statement>, do_sets: bool) -> Vec<Con
         ($path: path, $($tt: tt)*) => {
             {
                 let inst::BinaryBuiltin{a, b, into} = $($tt)*;
-                let stmt = Rc::new($path(l::BinaryBuiltin{a: a.clone(), b: b.clone(), into}));
+                let stmt = Arc::new($path(l::BinaryBuiltin{a: a.clone(), b: b.clone(), into}));
 
                 vec![
                     Condition::WhenChanges {
@@ -33,7 +34,7 @@ pub fn link_statement_list(statements: Vec<Statement>, do_sets: bool) -> Vec<Con
             inst::Statement::Not { input, into } => {
                 vec![Condition::WhenChanges {
                     variable: input.clone(),
-                    run: Rc::new(l::Statement::Not { input, into }),
+                    run: Arc::new(l::Statement::Not { input, into }),
                 }]
             }
             inst::Statement::And(b) => binary_stmt!(l::Statement::And, b),
@@ -45,21 +46,43 @@ pub fn link_statement_list(statements: Vec<Statement>, do_sets: bool) -> Vec<Con
             inst::Statement::Move(a, b) => {
                 vec![Condition::WhenChanges {
                     variable: b.clone(),
-                    run: Rc::new(l::Statement::Move(a, b)),
+                    run: Arc::new(l::Statement::Move(a, b)),
                 }]
             }
             inst::Statement::Set(a, b) => {
                 if do_sets {
                     vec![Condition::AtTime {
                         time: Instant::START,
-                        run: Rc::new(l::Statement::Set(a, b)),
+                        run: Arc::new(l::Statement::Set(a, b)),
                     }]
                 } else {
                     vec![]
                 }
             }
+            inst::Statement::Delayed(s, delay) => link_statement_list(vec![*s], do_sets)
+                .into_iter()
+                .map(|i| match i {
+                    Condition::WhenChanges { variable, run } => Condition::WhenChanges {
+                        variable,
+                        run: Arc::new(l::Statement::Delayed(run, delay)),
+                    },
+                    Condition::AtTime { time, run } => Condition::AtTime {
+                        time,
+                        run: Arc::new(l::Statement::Delayed(run, delay)),
+                    },
+                    i @ Condition::Window { .. } => i,
+                })
+                .collect(),
+            // forces only happen in processes, and are linked with the timed block
+            Statement::Force(_, _) => vec![],
             Statement::CreateCircuitInstance(circuit) => link_circuit(circuit),
-            Statement::Assert(_, _) => vec![], // ignore asserts in normal statements (shouldn't be parsed anyway)
+            // covers are watched for as long as the simulation runs, in circuits and tests alike
+            Statement::Cover(variable, bin) => vec![Condition::WhenChanges {
+                variable: variable.clone(),
+                run: Arc::new(l::Statement::Cover(variable, bin)),
+            }],
+            // ignore asserts in normal statements (shouldn't be parsed anyway)
+            Statement::Assert(_, _) | Statement::TemporalAssert(_, _, _) => vec![],
         })
         .collect()
 }
@@ -103,12 +126,31 @@ pub fn link_timed_block(p: inst::TimedBlock) -> Vec<Condition> {
         .filter_map(|i| match i {
             Statement::Set(a, b) => Some(Condition::AtTime {
                 time: p.time,
-                run: Rc::new(l::Statement::Set(a, b)),
+                run: Arc::new(l::Statement::Set(a, b)),
+            }),
+            Statement::Force(a, b) => Some(Condition::AtTime {
+                time: p.time,
+                run: Arc::new(l::Statement::Force(a, b)),
             }),
             Statement::Assert(e, span) => Some(Condition::AtTime {
-                time: p.time.add_process_step(),
-                run: Rc::new(l::Statement::Assert(e, span)),
+                time: p.time.observe(),
+                run: Arc::new(l::Statement::Assert(e, span)),
             }),
+            Statement::TemporalAssert(variable, span, temporal) => {
+                let (from, to, kind) = match temporal {
+                    Temporal::Always { from, to } => (from, to, WindowKind::Always),
+                    Temporal::Stable(d) => (p.time, p.time.after(&d), WindowKind::Stable),
+                    Temporal::Eventually(d) => (p.time, p.time.after(&d), WindowKind::Eventually),
+                };
+
+                Some(Condition::Window {
+                    variable,
+                    from: from.observe(),
+                    to: to.observe(),
+                    kind,
+                    span,
+                })
+            }
             _ => None,
         })
         .chain(rest)
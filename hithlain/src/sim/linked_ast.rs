@@ -1,19 +1,36 @@
-use crate::parse::ast::{Constant, Variable};
+use crate::parse::ast::{Constant, CoverBin, Variable};
 use crate::parse::span::Span;
 use crate::sim::instantiate::UniqueVariableRef;
-use crate::time::Instant;
-use std::rc::Rc;
+use crate::sim::value::Value;
+use crate::time::{Duration, Instant};
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum Condition {
     AtTime {
         time: Instant,
-        run: Rc<Statement>,
+        run: Arc<Statement>,
     },
     WhenChanges {
         variable: UniqueVariableRef,
-        run: Rc<Statement>,
+        run: Arc<Statement>,
     },
+    /// Watches a variable between two instants (inclusive),
+    /// checking it after every change.
+    Window {
+        variable: UniqueVariableRef,
+        from: Instant,
+        to: Instant,
+        kind: WindowKind,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WindowKind {
+    Always,
+    Stable,
+    Eventually,
 }
 
 #[derive(Debug)]
@@ -26,6 +43,8 @@ pub struct BinaryBuiltin {
 #[derive(Debug)]
 pub enum Statement {
     Assert(UniqueVariableRef, Span),
+    /// checked once a step has settled, like temporal assertions
+    Cover(UniqueVariableRef, CoverBin),
     Not {
         input: UniqueVariableRef,
         into: UniqueVariableRef,
@@ -38,6 +57,72 @@ pub enum Statement {
     Xnor(BinaryBuiltin),
     Move(UniqueVariableRef, UniqueVariableRef),
     Set(UniqueVariableRef, Constant),
+    Force(UniqueVariableRef, Constant),
+
+    /// evaluates a statement, but only writes its outcome after a delay
+    Delayed(Arc<Statement>, Duration),
+
+    // the following statements are scheduled by the simulation itself.
+    // windows are referred to by index
+    WindowStart(usize),
+    WindowCheck(usize),
+    WindowEnd(usize),
+    /// the outcome of a delayed statement. When delays are inertial, only
+    /// the write of the most recent generation for a variable happens.
+    Write {
+        into: UniqueVariableRef,
+        value: Value,
+        generation: u64,
+    },
+}
+
+impl Statement {
+    /// The variable this statement writes to, if any.
+    #[must_use]
+    pub fn writes(&self) -> Option<&UniqueVariableRef> {
+        match self {
+            Statement::Not { into, .. }
+            | Statement::And(BinaryBuiltin { into, .. })
+            | Statement::Or(BinaryBuiltin { into, .. })
+            | Statement::Nand(BinaryBuiltin { into, .. })
+            | Statement::Nor(BinaryBuiltin { into, .. })
+            | Statement::Xor(BinaryBuiltin { into, .. })
+            | Statement::Xnor(BinaryBuiltin { into, .. }) => Some(into),
+            Statement::Move(a, _)
+            | Statement::Set(a, _)
+            | Statement::Force(a, _)
+            | Statement::Write { into: a, .. } => Some(a),
+            Statement::Delayed(s, _) => s.writes(),
+            Statement::Assert(..)
+            | Statement::Cover(..)
+            | Statement::WindowStart(_)
+            | Statement::WindowCheck(_)
+            | Statement::WindowEnd(_) => None,
+        }
+    }
+
+    /// The variables this statement reads. Windows read the variable they watch,
+    /// but that isn't part of the statement.
+    #[must_use]
+    pub fn reads(&self) -> Vec<&UniqueVariableRef> {
+        match self {
+            Statement::Assert(v, _) | Statement::Cover(v, _) | Statement::Move(_, v) => vec![v],
+            Statement::Not { input, .. } => vec![input],
+            Statement::And(b)
+            | Statement::Or(b)
+            | Statement::Nand(b)
+            | Statement::Nor(b)
+            | Statement::Xor(b)
+            | Statement::Xnor(b) => vec![&b.a, &b.b],
+            Statement::Delayed(s, _) => s.reads(),
+            Statement::Set(..)
+            | Statement::Force(..)
+            | Statement::Write { .. }
+            | Statement::WindowStart(_)
+            | Statement::WindowCheck(_)
+            | Statement::WindowEnd(_) => vec![],
+        }
+    }
 }
 
 pub struct Process {
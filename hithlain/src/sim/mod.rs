@@ -1,25 +1,52 @@
-use miette::Diagnostic;
+use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 
-use simulation::{AssertionError, Simulation, SimulationState};
+use simulation::{AssertionError, Oscillation, Simulation, SimulationState};
 
-use crate::parse::desugared_ast::{Process, Program};
+use crate::analyze::find_circuit;
+use crate::error::{did_you_mean, Warn};
+use crate::parse::desugared_ast::{Check, Process, Program};
 
+use crate::sim::check::{check_to_process, counterexample, Combinations, CounterExample};
+use crate::sim::compiled::NotLevelisable;
 use crate::sim::config::SimulationConfig;
-use crate::sim::instantiate::instantiate_program;
-use crate::sim::link::link_process;
-use crate::sim::value::ValueError;
-use crate::vcd::vcd_ast::process_to_vcd_ast;
+use crate::sim::fault::{inject_faults, Fault};
+use crate::sim::instantiate::{
+    instantiate_circuit, instantiate_program, UniqueVariableRefGenerator,
+};
+use crate::sim::interactive::{Interactive, UnknownCircuit, UnknownSignal};
+use crate::sim::link::{link_circuit, link_process};
+use crate::sim::linked_ast as l;
+use crate::sim::report::{TestReport, TestStatus};
+use crate::sim::signal::EventOrder;
+use crate::sim::snapshot::SnapshotError;
+use crate::sim::value::{Value, ValueError};
+use crate::time::Duration;
+use crate::vcd::vcd_ast::{circuit_to_vcd_ast, process_to_vcd_ast};
 use crate::vcd::VcdError;
-use std::rc::Rc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
 
+pub mod check;
+pub mod compiled;
 pub mod config;
+pub mod coverage;
+pub mod fault;
+pub mod glitch;
 pub mod instantiate;
 pub mod instantiated_ast;
+pub mod interactive;
 pub mod link;
 pub mod linked_ast;
+pub mod observer;
+pub mod power;
+pub mod report;
 pub mod signal;
 pub mod simulation;
+pub mod snapshot;
 pub mod value;
 
 #[derive(Debug, Error, Diagnostic)]
@@ -28,6 +55,58 @@ pub enum SimulationError {
     #[diagnostic(transparent)]
     AssertionError(#[from] AssertionError),
 
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    AssertionsFailed(#[from] AssertionsFailed),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    TestsFailed(#[from] TestsFailed),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    CounterExample(#[from] CounterExample),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    ShouldHaveFailed(#[from] ShouldHaveFailed),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    TestTimedOut(#[from] TestTimedOut),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Oscillation(#[from] Oscillation),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    NotLevelisable(#[from] NotLevelisable),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    RaceDetected(#[from] RaceDetected),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    SnapshotError(#[from] SnapshotError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnknownTest(#[from] UnknownTest),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnknownCheck(#[from] UnknownCheck),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnknownCircuit(#[from] UnknownCircuit),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnknownSignal(#[from] UnknownSignal),
+
     #[error(transparent)]
     #[diagnostic(transparent)]
     ValueError(#[from] ValueError),
@@ -37,6 +116,175 @@ pub enum SimulationError {
     VcdError(#[from] VcdError),
 }
 
+#[derive(Error, Debug, Diagnostic)]
+#[error(
+    "{} assertion(s) failed in {}{}",
+    failures.len(),
+    name,
+    fatal.as_ref().map(|e| format!(", before: {}", e)).unwrap_or_default()
+)]
+#[diagnostic()]
+pub struct AssertionsFailed {
+    name: String,
+
+    #[related]
+    pub(crate) failures: Vec<AssertionError>,
+
+    /// what stopped the simulation after the assertions failed, like a timeout
+    #[source]
+    pub(crate) fatal: Option<Box<SimulationError>>,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("{} of {} {} failed", errors.len(), total, kind)]
+#[diagnostic()]
+pub struct TestsFailed {
+    kind: &'static str,
+    total: usize,
+
+    #[related]
+    pub(crate) errors: Vec<SimulationError>,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("test {} passed, but should have failed", name)]
+#[diagnostic(help("remove #[should_fail] if this test is supposed to pass"))]
+pub struct ShouldHaveFailed {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("this test")]
+    span: SourceSpan,
+
+    name: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("test {} still running after {}ns", name, timeout)]
+#[diagnostic()]
+pub struct TestTimedOut {
+    #[source_code]
+    src: NamedSource,
+
+    #[label("this test")]
+    span: SourceSpan,
+
+    name: String,
+    timeout: u64,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("simulation of {} stopped at the time limit of {}ns", name, limit)]
+#[diagnostic(
+    severity = "warning",
+    help(
+        "the last event ran at {}ns, {} event(s) were still pending",
+        reached,
+        pending
+    )
+)]
+pub struct TimeLimitReached {
+    name: String,
+    limit: u64,
+    reached: u64,
+    pending: usize,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("no test named {}", name)]
+#[diagnostic(help("{}available tests: {}", suggestion, available))]
+pub struct UnknownTest {
+    name: String,
+    suggestion: String,
+    available: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("no check of a circuit named {}", name)]
+#[diagnostic(help("{}available checks: {}", suggestion, available))]
+pub struct UnknownCheck {
+    name: String,
+    suggestion: String,
+    available: String,
+}
+
+/// Calls `run` for every item on `jobs` threads, and returns the outcomes in the same order.
+/// Once `stop` is true for an outcome, no new items are started, so only the first items
+/// may have an outcome.
+pub(crate) fn parallel_map<T: Sync, R: Send>(
+    jobs: usize,
+    items: &[T],
+    stop: impl Fn(&R) -> bool + Sync,
+    run: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+
+    let mut res: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut res = Vec::new();
+                    while !stopped.load(Ordering::SeqCst) {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+                        let item = match items.get(index) {
+                            Some(item) => item,
+                            None => break,
+                        };
+
+                        let outcome = run(item);
+                        if stop(&outcome) {
+                            stopped.store(true, Ordering::SeqCst);
+                        }
+
+                        res.push((index, outcome));
+                    }
+                    res
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|i| i.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+    res.sort_by_key(|(index, _)| *index);
+    res.into_iter().map(|(_, i)| i).collect()
+}
+
+/// A suggestion for a misspelled name, and a list of all the names.
+fn suggest<'a>(name: &str, names: impl Iterator<Item = &'a str> + Clone) -> (String, String) {
+    (
+        did_you_mean(name, names.clone())
+            .map(|i| format!("did you mean `{}`? ", i))
+            .unwrap_or_default(),
+        names.collect::<Vec<_>>().join(", "),
+    )
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(
+    "the outcome of test {} depends on the order of events at the same time",
+    name
+)]
+#[diagnostic(help(
+    "with those events in reverse order, {}. signals should have one driver at a time",
+    reason
+))]
+pub struct RaceDetected {
+    name: String,
+    reason: String,
+}
+
+/// How a process is simulated again after its first run.
+enum Rerun<'a> {
+    /// with events at the same time in a different order
+    Reordered(EventOrder),
+    /// with signals stuck at a value
+    Faulty(&'a [Fault]),
+}
+
 pub struct Simulator {
     program: Program,
 
@@ -48,40 +296,407 @@ impl Simulator {
         Ok(Self { program, config })
     }
 
-    pub fn run_test(&self, name: impl AsRef<str>) -> Result<(), SimulationError> {
-        for i in &self.program.tests {
-            if i.name.0 == name.as_ref() {
-                self._run_test(i)?;
+    /// Runs a single test, even if it's ignored.
+    pub fn run_test(&self, name: impl AsRef<str>) -> Result<TestReport, SimulationError> {
+        let name = name.as_ref();
+        let test = self
+            .program
+            .tests
+            .iter()
+            .find(|i| i.name.0 == name)
+            .ok_or_else(|| {
+                let (suggestion, available) =
+                    suggest(name, self.program.tests.iter().map(|i| i.name.0.as_str()));
+                UnknownTest {
+                    name: name.to_string(),
+                    suggestion,
+                    available,
+                }
+            })?;
+
+        Ok(self._run_test(test))
+    }
+
+    fn _run_test(&self, test: &Arc<Process>) -> TestReport {
+        let mut report = TestReport::new(&test.name.0);
+        let start = std::time::Instant::now();
+        let res = match self.execute_process(test, &mut report, None, None) {
+            Ok(values) if self.config.detect_races => self.detect_race(test, &values),
+            res => res.map(|_| ()),
+        };
+        report.wall_time = start.elapsed();
+
+        let res = match res {
+            Err(SimulationError::AssertionError(_)) if test.attributes.should_fail => Ok(()),
+            Err(SimulationError::AssertionsFailed(e))
+                if test.attributes.should_fail && e.fatal.is_none() =>
+            {
+                Ok(())
+            }
+            Ok(()) if test.attributes.should_fail => {
+                let span = test.name.1.clone().expect("test name must have a span");
+                Err(ShouldHaveFailed {
+                    src: span.source().clone().into(),
+                    span: span.into(),
+                    name: test.name.0.clone(),
+                }
+                .into())
+            }
+            res => res,
+        };
+
+        report.finish(res);
+        report
+    }
+
+    /// Runs every test that isn't ignored.
+    pub fn run_all_tests(&self) -> Result<(), SimulationError> {
+        self.run_tests_with_tags(&[])
+    }
+
+    /// Runs every test that isn't ignored and has at least one of the tags.
+    /// When no tags are given, all tests that aren't ignored are run.
+    pub fn run_tests_with_tags(&self, tags: &[String]) -> Result<(), SimulationError> {
+        self.reports_to_result("tests", self.run_tests(tags))
+    }
+
+    /// Like [`Simulator::run_tests_with_tags`], but reports on every test with one of the tags,
+    /// including ignored ones, instead of only returning the failures.
+    #[must_use]
+    pub fn run_tests(&self, tags: &[String]) -> Vec<TestReport> {
+        let tests: Vec<_> = self
+            .program
+            .tests
+            .iter()
+            .filter(|i| tags.is_empty() || i.attributes.tags.iter().any(|t| tags.contains(t)))
+            .collect();
+
+        self.run_in_parallel(
+            &tests,
+            |i| i.name.0.as_str(),
+            |i| {
+                if i.attributes.ignore {
+                    TestReport::new(&i.name.0)
+                } else {
+                    self._run_test(i)
+                }
+            },
+        )
+    }
+
+    /// Runs tests (or checks) on `config.jobs` threads. The reports are in the same order as
+    /// the tests, so the output doesn't depend on which test finishes first. With `fail_fast`,
+    /// no new tests are started after a failure, and the remaining tests are reported as skipped.
+    fn run_in_parallel<T: Sync>(
+        &self,
+        tests: &[T],
+        name: impl Fn(&T) -> &str,
+        run: impl Fn(&T) -> TestReport + Sync,
+    ) -> Vec<TestReport> {
+        let mut res = parallel_map(
+            self.config.jobs,
+            tests,
+            |report: &TestReport| self.config.fail_fast && report.status == TestStatus::Failed,
+            run,
+        );
+        if self.config.fail_fast {
+            // tests after the first failure may have finished on other threads
+            if let Some(first) = res.iter().position(|i| i.status == TestStatus::Failed) {
+                res.truncate(first + 1);
             }
         }
-        Ok(())
+
+        for i in &tests[res.len()..] {
+            res.push(TestReport::new(name(i)));
+        }
+        res
     }
 
-    fn _run_test(&self, test: &Rc<Process>) -> Result<(), SimulationError> {
-        self.execute_process(test)
+    /// Instantiates a circuit as the top level of a simulation that is driven from Rust.
+    pub fn instantiate(
+        &self,
+        circuit: impl AsRef<str>,
+    ) -> Result<Interactive<'_>, SimulationError> {
+        let circuit = find_circuit(&self.program, circuit.as_ref())?;
+
+        let instantiated =
+            instantiate_circuit(circuit, &mut UniqueVariableRefGenerator::new(), vec![]);
+
+        let vcd_ast = if self.config.create_vcd {
+            Some(circuit_to_vcd_ast(&instantiated))
+        } else {
+            None
+        };
+
+        let linked = l::Process {
+            name: circuit.name.clone(),
+            conditions: link_circuit(instantiated.clone()),
+        };
+
+        let simulation = Simulation::new(linked, &self.config, vcd_ast)?;
+        Ok(Interactive::new(simulation, &instantiated))
     }
 
-    pub fn run_all_tests(&self) -> Result<(), SimulationError> {
-        for i in &self.program.tests {
-            self._run_test(i)?;
+    /// Runs every check of a circuit.
+    pub fn run_check(&self, circuit: impl AsRef<str>) -> Result<(), SimulationError> {
+        let circuit = circuit.as_ref();
+        let checks: Vec<_> = self
+            .program
+            .checks
+            .iter()
+            .filter(|i| i.name.0 == circuit)
+            .collect();
+        if checks.is_empty() {
+            let (suggestion, available) = suggest(
+                circuit,
+                self.program.checks.iter().map(|i| i.name.0.as_str()),
+            );
+            return Err(UnknownCheck {
+                name: circuit.to_string(),
+                suggestion,
+                available,
+            }
+            .into());
         }
 
+        for i in checks {
+            let report = self._run_check(i);
+            report.warnings.iter().for_each(Warn::warn);
+            report.into_result()?;
+        }
         Ok(())
     }
 
-    fn execute_process(&self, test: &Rc<Process>) -> Result<(), SimulationError> {
+    fn _run_check(&self, check: &Arc<Check>) -> TestReport {
+        let mut report = TestReport::new(&check.name.0);
+        let start = std::time::Instant::now();
+        let res = self
+            .execute_process(
+                &Arc::new(check_to_process(check)),
+                &mut report,
+                None,
+                Some(check),
+            )
+            .map(|_| ());
+        report.wall_time = start.elapsed();
+
+        let res = match res {
+            Err(SimulationError::AssertionError(e)) => Err(counterexample(check, e).into()),
+            // only the first counterexample is reported, there may be very many
+            Err(SimulationError::AssertionsFailed(e)) => {
+                let first = e
+                    .failures
+                    .into_iter()
+                    .next()
+                    .expect("at least one assertion failed");
+                Err(counterexample(check, first).into())
+            }
+            res => res,
+        };
+
+        report.finish(res);
+        report
+    }
+
+    pub fn run_all_checks(&self) -> Result<(), SimulationError> {
+        self.reports_to_result("checks", self.run_checks())
+    }
+
+    /// Like [`Simulator::run_all_checks`], but reports on every check.
+    #[must_use]
+    pub fn run_checks(&self) -> Vec<TestReport> {
+        self.run_in_parallel(
+            &self.program.checks,
+            |i| i.name.0.as_str(),
+            |i| self._run_check(i),
+        )
+    }
+
+    /// Turns reports into a single result, printing their warnings. Like the reports,
+    /// failures are in the order the tests are written in.
+    fn reports_to_result(
+        &self,
+        kind: &'static str,
+        reports: Vec<TestReport>,
+    ) -> Result<(), SimulationError> {
+        let total = reports
+            .iter()
+            .filter(|i| i.status != TestStatus::Skipped)
+            .count();
+
+        let mut errors = Vec::new();
+        for i in reports {
+            i.warnings.iter().for_each(Warn::warn);
+            if let Some(e) = i.failure {
+                errors.push(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else if self.config.fail_fast {
+            Err(errors.remove(0))
+        } else {
+            Err(TestsFailed {
+                kind,
+                total,
+                errors,
+            }
+            .into())
+        }
+    }
+
+    /// Simulates a process. Warnings and statistics are collected in the report
+    /// instead of printed, so tests running in parallel don't mix their output.
+    /// Returns the final value of every signal.
+    ///
+    /// A rerun simulates the process again without vcd file and observers,
+    /// to compare it with the first run. A check applies its other combinations of inputs
+    /// while the process runs.
+    fn execute_process(
+        &self,
+        test: &Arc<Process>,
+        report: &mut TestReport,
+        rerun: Option<Rerun<'_>>,
+        check: Option<&Check>,
+    ) -> Result<BTreeMap<(String, usize), Value>, SimulationError> {
         let instantiated = instantiate_program(test);
 
-        let vcd_ast = if self.config.create_vcd {
+        let vcd_ast = if self.config.create_vcd && rerun.is_none() {
             Some(process_to_vcd_ast(&instantiated))
         } else {
             None
         };
 
-        let linked = link_process(instantiated);
+        let mut linked = link_process(instantiated);
+        let mut combinations = check.map(|i| Combinations::new(i, &linked));
+
+        let mut simulation = match rerun {
+            Some(Rerun::Reordered(order)) => {
+                Simulation::with_event_order(linked, &self.config, vcd_ast, order)?
+                    .without_observers()
+            }
+            Some(Rerun::Faulty(faults)) => {
+                inject_faults(&mut linked, faults);
+                Simulation::new(linked, &self.config, vcd_ast)?.without_observers()
+            }
+            None => Simulation::new(linked, &self.config, vcd_ast)?,
+        };
+        let res = self.run_to_end(test, &mut simulation, report, combinations.as_mut());
+
+        report.assertions = simulation.assertions();
+        report.covers = simulation.covers().to_vec();
+        report.simulated = Duration::from_nanos(simulation.last_instant().nanos());
+
+        // failures from before a timeout or oscillation are still reported
+        let failures = simulation.take_failures();
+        match res {
+            Err(e) if !failures.is_empty() => {
+                return Err(AssertionsFailed {
+                    name: test.name.0.clone(),
+                    failures,
+                    fatal: Some(Box::new(e)),
+                }
+                .into())
+            }
+            Err(e) => return Err(e),
+            Ok(()) if !failures.is_empty() => {
+                return Err(AssertionsFailed {
+                    name: test.name.0.clone(),
+                    failures,
+                    fatal: None,
+                }
+                .into())
+            }
+            Ok(()) => {}
+        }
+
+        Ok(simulation.final_values())
+    }
+
+    /// Simulates a test again with events at the same time in reverse order, and compares the
+    /// final value of every signal with the first run. Any difference means the outcome depends
+    /// on the order of those events.
+    fn detect_race(
+        &self,
+        test: &Arc<Process>,
+        values: &BTreeMap<(String, usize), Value>,
+    ) -> Result<(), SimulationError> {
+        let mut report = TestReport::new(&test.name.0);
+        let reason = match self.execute_process(
+            test,
+            &mut report,
+            Some(Rerun::Reordered(EventOrder::reversed())),
+            None,
+        ) {
+            Ok(shuffled) => {
+                let signals: BTreeSet<_> = values
+                    .keys()
+                    .chain(shuffled.keys())
+                    .filter(|i| values.get(*i) != shuffled.get(*i))
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+
+                if signals.is_empty() {
+                    return Ok(());
+                }
+                format!(
+                    "these signals ended with a different value: {}",
+                    signals.into_iter().collect::<Vec<_>>().join(", ")
+                )
+            }
+            Err(e) => format!("the test failed: {}", e),
+        };
+
+        Err(RaceDetected {
+            name: test.name.0.clone(),
+            reason,
+        }
+        .into())
+    }
+
+    /// Steps through a simulation until there are no more events, or a limit is reached.
+    fn run_to_end(
+        &self,
+        test: &Process,
+        simulation: &mut Simulation,
+        report: &mut TestReport,
+        mut combinations: Option<&mut Combinations>,
+    ) -> Result<(), SimulationError> {
+        loop {
+            if let Some(c) = &mut combinations {
+                c.advance(simulation);
+            }
+
+            if let (Some(timeout), Some(next)) =
+                (test.attributes.timeout, simulation.next_instant())
+            {
+                if next.nanos() > timeout.nanos() {
+                    let span = test.name.1.clone().expect("test name must have a span");
+                    return Err(TestTimedOut {
+                        src: span.source().clone().into(),
+                        span: span.into(),
+                        name: test.name.0.clone(),
+                        timeout: timeout.nanos(),
+                    }
+                    .into());
+                }
+            }
 
-        let mut simulation = Simulation::new(linked, &self.config, vcd_ast)?;
-        while let SimulationState::Continue = simulation.step()? {}
+            match simulation.step()? {
+                SimulationState::Continue => {}
+                SimulationState::End => break,
+                SimulationState::TimeLimit { reached, pending } => {
+                    report.warnings.push(TimeLimitReached {
+                        name: test.name.0.clone(),
+                        limit: self.config.simulation_time.map_or(0, |i| i.nanos()),
+                        reached: reached.nanos(),
+                        pending,
+                    });
+                    break;
+                }
+            }
+        }
 
         Ok(())
     }
@@ -94,8 +709,11 @@ mod tests {
     use crate::parse::lexer::lex;
     use crate::parse::parser::Parser;
     use crate::parse::source::Source;
-    use crate::sim::config::SimulationConfig;
-    use crate::sim::Simulator;
+    use crate::sim::config::{DelayMode, Engine, SimulationConfig, VcdPath};
+    use crate::sim::report::{CoverTable, Summary, TestReport, TestStatus};
+    use crate::sim::{SimulationError, Simulator};
+    use crate::time::Duration;
+    use miette::Diagnostic;
 
     #[test]
     fn test_smoke() {
@@ -179,4 +797,853 @@ mod tests {
         let s = Simulator::new(desugared, config).nice_unwrap_panic();
         s.run_all_tests().nice_unwrap_panic();
     }
+
+    #[test]
+    fn test_check() {
+        let src = "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or ((a xor b) and c_in);
+        }
+
+        check add for all inputs {
+            assert o == a xor b xor c_in;
+            assert c_out == ((a and b) or (c_in and (a or b)));
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        s.run_all_checks().nice_unwrap_panic();
+
+        // both assertions for each of the 8 combinations
+        let reports = s.run_checks();
+        assert_eq!(reports[0].assertions(), 16);
+    }
+
+    #[test]
+    fn test_check_counterexample() {
+        let src = "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or (a and c_in);
+        }
+
+        check add for all inputs {
+            assert c_out == ((a and b) or (c_in and (a or b)));
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        match s.run_check("add") {
+            Err(SimulationError::CounterExample(e)) => {
+                assert_eq!(e.assignment, "a = 0, b = 1, c_in = 1");
+            }
+            _ => panic!("expected a counterexample"),
+        }
+
+        match s.run_check("ad") {
+            Err(SimulationError::UnknownCheck(e)) => {
+                assert_eq!(e.suggestion, "did you mean `add`? ");
+            }
+            _ => panic!("expected an unknown check"),
+        }
+    }
+
+    #[test]
+    fn test_temporal_asserts() {
+        let src = "
+        circuit xor_gate: a b -> o {
+            o = a xor b;
+        }
+
+        test main {
+            o = xor_gate(a, b);
+
+            at 0ns:
+                a = 0;
+                b = 0;
+
+                assert always (o == (a xor b)) from 0ns to 20ns;
+                assert stable o during 5ns;
+
+            after 10ns:
+                a = 1;
+
+                assert eventually o within 5ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+    }
+
+    #[test]
+    fn test_temporal_assert_fails() {
+        let src = "
+        circuit xor_gate: a b -> o {
+            o = a xor b;
+        }
+
+        test main {
+            o = xor_gate(a, b);
+
+            at 0ns:
+                a = 0;
+                b = 0;
+
+                assert stable o during 20ns;
+
+            after 10ns:
+                a = 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        match s.run_test("main").and_then(TestReport::into_result) {
+            Err(SimulationError::AssertionsFailed(e)) => {
+                assert_eq!(e.failures.len(), 1);
+                assert_eq!(e.failures[0].time.nanos(), 10);
+                assert_eq!(e.failures[0].reason, "changed");
+            }
+            _ => panic!("expected the stable assertion to fail"),
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let src = "
+        circuit and_gate: a b -> o {
+            o = a and b;
+        }
+
+        #[should_fail]
+        test broken {
+            o = and_gate(a, b);
+
+            at 0ns:
+                a = 1;
+                b = 0;
+
+                assert o == 1;
+        }
+
+        #[ignore]
+        test ignored {
+            o = and_gate(a, b);
+
+            at 0ns:
+                a = 1;
+                b = 1;
+
+                assert o == 0;
+        }
+
+        #[tag(slow)]
+        #[timeout(10ns)]
+        test slow {
+            o = and_gate(a, b);
+
+            at 0ns:
+                a = 1;
+                b = 1;
+
+            after 20ns:
+                assert o == 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        s.run_tests_with_tags(&["fast".to_string()])
+            .nice_unwrap_panic();
+        s.run_test("broken")
+            .and_then(TestReport::into_result)
+            .nice_unwrap_panic();
+        match s.run_all_tests() {
+            Err(SimulationError::TestsFailed(e)) => {
+                assert_eq!(e.errors.len(), 1);
+                assert!(matches!(e.errors[0], SimulationError::TestTimedOut(_)));
+            }
+            _ => panic!("expected only the slow test to fail"),
+        }
+    }
+
+    #[test]
+    fn test_collect_failures() {
+        let src = "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or (a and c_in);
+        }
+
+        test main {
+            o, c_out = add(a, b, c_in);
+
+            at 0ns:
+                a = 0;
+                b = 1;
+                c_in = 1;
+
+                assert c_out == 1;
+
+            after 5ns:
+                a = 1;
+                b = 1;
+                c_in = 0;
+
+                assert c_out == 1;
+
+            after 5ns:
+                a = 0;
+                b = 1;
+                c_in = 1;
+
+                assert c_out == 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        match s.run_test("main").and_then(TestReport::into_result) {
+            Err(SimulationError::AssertionsFailed(e)) => {
+                let times: Vec<_> = e.failures.iter().map(|i| i.time.nanos()).collect();
+                assert_eq!(times, vec![0, 10]);
+            }
+            _ => panic!("expected two failing assertions"),
+        }
+
+        let config = SimulationConfig {
+            fail_fast: true,
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        match s.run_test("main").and_then(TestReport::into_result) {
+            Err(SimulationError::AssertionError(e)) => assert_eq!(e.time.nanos(), 0),
+            _ => panic!("expected the first assertion to fail"),
+        }
+
+        // a timeout doesn't hide the failures before it
+        let src = "
+        #[timeout(7ns)]
+        test main {
+            at 0ns:
+                a = 0;
+                assert a == 1;
+
+            after 10ns:
+                a = 1;
+        }
+        ";
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+        let desugared =
+            desugar_program(&parser.parse_program().nice_unwrap_panic()).nice_unwrap_panic();
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        match s.run_test("main").and_then(TestReport::into_result) {
+            Err(SimulationError::AssertionsFailed(e)) => {
+                assert_eq!(e.failures.len(), 1);
+                assert!(matches!(
+                    e.fatal.as_deref(),
+                    Some(SimulationError::TestTimedOut(_))
+                ));
+            }
+            _ => panic!("expected the failure and the timeout"),
+        }
+    }
+
+    #[test]
+    fn test_hierarchical_references() {
+        let src = "
+        circuit and_gate: a b -> o {
+            o = a and b;
+        }
+
+        circuit add: a b c_in -> o c_out {
+            x = a xor b;
+            o = x xor c_in;
+            carry = and_gate(a, b);
+            c_out = carry or (x and c_in);
+        }
+
+        test main {
+            o, c_out = add(a, b, c_in);
+
+            at 0ns:
+                a = 1;
+                b = 0;
+                c_in = 0;
+
+                assert add.x == 1;
+                assert add.and_gate.o == 0;
+
+            after 5ns:
+                force add.x = 0;
+
+                assert add.x == 0;
+                assert o == 0;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+    }
+
+    #[test]
+    fn test_simulation_time() {
+        let src = "
+        test main {
+            at 0ns:
+                a = 1;
+
+                assert a == 1;
+
+            at 100ns:
+                a = 0;
+
+                assert a == 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let config = SimulationConfig {
+            simulation_time: Some(Duration::from_nanos(50)),
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        let s = Simulator::new(
+            desugar_program(&parsed).nice_unwrap_panic(),
+            SimulationConfig::default(),
+        )
+        .nice_unwrap_panic();
+        assert!(s.run_all_tests().is_err());
+    }
+
+    #[test]
+    fn test_oscillation() {
+        // the loop goes through a hierarchical reference, which isn't
+        // visible to the static check
+        let src = "
+        circuit inv: a -> o {
+            x = not(a);
+            o = x;
+        }
+
+        test main {
+            at 0ns:
+                i = 0;
+                o = inv(i);
+                i = inv.x;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        match s.run_all_tests() {
+            Err(SimulationError::TestsFailed(e)) => match &e.errors[..] {
+                [SimulationError::Oscillation(e)] => {
+                    assert!(e
+                        .to_string()
+                        .starts_with("signals kept changing for 1001 delta cycles at 0ns"));
+                }
+                other => panic!("expected an oscillation, got {:?}", other),
+            },
+            Err(e) => panic!("expected an oscillation, got {:?}", e),
+            Ok(()) => panic!("expected an oscillation"),
+        }
+    }
+
+    #[test]
+    fn test_deep_chains() {
+        // a long and a short chain of inverters meet in an xor. while the long one settles,
+        // the xor glitches, but assertions only ever see the settled values. every write in
+        // a test happens in the first process step of its instant, so this also held when
+        // assertions ran in the next process step instead of the observe region.
+        let stages = 100;
+        let long: String = (0..stages)
+            .map(|i| format!("l{} = inv(l{});\n", i + 1, i))
+            .collect();
+        let src = format!(
+            "
+        circuit inv: a -> o {{
+            o = not(a);
+        }}
+
+        test deep {{
+            l0 = a;
+            {long}
+            s0 = inv(a);
+            s = inv(s0);
+            d = l{stages} xor s;
+
+            at 0ns:
+                a = 1;
+                assert l{stages} == 1;
+                assert d == 0;
+                assert always (d == 0) from 0ns to 3ns;
+            at 1ns:
+                a = 0;
+                assert l{stages} == 0;
+                assert d == 0;
+                assert stable l{stages} during 1ns;
+            at 3ns:
+                a = 1;
+                assert l{stages} == 1;
+                assert d == 0;
+        }}
+        "
+        );
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        for engine in [Engine::EventDriven, Engine::Compiled] {
+            let config = SimulationConfig {
+                engine,
+                ..SimulationConfig::default()
+            };
+            let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+                .nice_unwrap_panic();
+
+            let report = s.run_test("deep").nice_unwrap_panic();
+            assert_eq!(report.assertions(), 8);
+            report.into_result().nice_unwrap_panic();
+        }
+    }
+
+    #[test]
+    fn test_delays() {
+        let src = "
+        circuit slow: a b -> o {
+            o = a and b after 2ns;
+        }
+
+        #[delay(1ns)]
+        circuit chain: a -> o {
+            x = not(a);
+            o = not(x);
+        }
+
+        test main {
+            o = slow(a, b);
+            p = chain(a);
+
+            at 0ns:
+                a = 0;
+                b = 0;
+            at 10ns:
+                a = 1;
+                b = 1;
+            at 11ns:
+                assert o == 0;
+                assert p == 0;
+            at 12ns:
+                assert o == 1;
+                assert p == 1;
+        }
+
+        check slow for all inputs {
+            assert o == (a and b);
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+        s.run_all_checks().nice_unwrap_panic();
+    }
+
+    #[test]
+    fn test_inertial_and_transport() {
+        let src = "
+        circuit buf: a -> o {
+            o = a after 3ns;
+        }
+
+        test pulse {
+            o = buf(a);
+
+            at 0ns:
+                a = 0;
+                assert always (o == 0) from 4ns to 20ns;
+            at 5ns:
+                a = 1;
+            at 6ns:
+                a = 0;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        // the pulse is shorter than the delay, so it's swallowed
+        let s = Simulator::new(
+            desugar_program(&parsed).nice_unwrap_panic(),
+            SimulationConfig::default(),
+        )
+        .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        let config = SimulationConfig {
+            delay_mode: DelayMode::Transport,
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        assert!(s.run_all_tests().is_err());
+    }
+
+    #[test]
+    fn test_parallel() {
+        // every third test fails, with the failing test's name in the error
+        let mut src = String::from(
+            "
+            circuit add: a b c_in -> o c_out {
+                o = a xor b xor c_in;
+                c_out = (a and b) or ((a xor b) and c_in);
+            }
+            ",
+        );
+        for i in 0..30 {
+            let expected = if i % 3 == 0 { 0 } else { 1 };
+            src.push_str(&format!(
+                "
+                test t{} {{
+                    o, c_out = add(a, b, 0);
+
+                    at 0ns:
+                        a = 1;
+                        b = 0;
+                        assert o == {};
+                }}
+                ",
+                i, expected
+            ));
+        }
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let failures = |jobs| {
+            let config = SimulationConfig {
+                jobs,
+                ..SimulationConfig::default()
+            };
+            let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+                .nice_unwrap_panic();
+
+            match s.run_all_tests() {
+                Err(SimulationError::TestsFailed(e)) => {
+                    e.errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+                }
+                other => panic!("expected failing tests, got {:?}", other),
+            }
+        };
+
+        let sequential = failures(1);
+        assert_eq!(sequential.len(), 10);
+        assert_eq!(sequential[1], "1 assertion(s) failed in t3");
+        assert_eq!(failures(4), sequential);
+    }
+
+    #[test]
+    fn test_vcd_per_test() {
+        let src = "
+        test first {
+            at 0ns:
+                a = 1;
+        }
+
+        test second {
+            at 0ns:
+                b = 0;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let dir = std::env::temp_dir().join(format!("hithlain-vcd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config = SimulationConfig {
+            vcd_path: VcdPath::Directory(dir.clone()),
+            jobs: 2,
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_all_tests().nice_unwrap_panic();
+
+        let first = std::fs::read_to_string(dir.join("first.vcd")).unwrap();
+        let second = std::fs::read_to_string(dir.join("second.vcd")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(first.contains(" a $end"));
+        assert!(second.contains(" b $end"));
+    }
+
+    #[test]
+    fn test_reports() {
+        let src = "
+        circuit and_gate: a b -> o {
+            o = a and b;
+        }
+
+        test passes {
+            o = and_gate(a, b);
+
+            at 0ns:
+                a = 1;
+                b = 1;
+                assert o == 1;
+            at 5ns:
+                assert always o from 5ns to 10ns;
+        }
+
+        test fails {
+            o = and_gate(a, b);
+
+            at 0ns:
+                a = 1;
+                b = 0;
+                assert o == 1;
+        }
+
+        #[ignore]
+        test ignored {
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+        let desugared = desugar_program(&parsed).nice_unwrap_panic();
+
+        let s = Simulator::new(desugared, SimulationConfig::default()).nice_unwrap_panic();
+        let reports = s.run_tests(&[]);
+
+        let statuses: Vec<_> = reports.iter().map(|i| (i.name(), i.status())).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("passes", TestStatus::Passed),
+                ("fails", TestStatus::Failed),
+                ("ignored", TestStatus::Skipped)
+            ]
+        );
+        assert_eq!(reports[0].assertions(), 2);
+        assert_eq!(reports[0].simulated().nanos(), 10);
+        assert!(reports[0].failure().is_none());
+        assert!(matches!(
+            reports[1].failure(),
+            Some(SimulationError::AssertionsFailed(_))
+        ));
+
+        let summary = Summary::new(&reports);
+        assert_eq!(
+            summary.to_string(),
+            "test result: FAILED. 1 passed; 1 failed; 1 skipped"
+        );
+
+        match s.run_test("fail") {
+            Err(SimulationError::UnknownTest(e)) => assert_eq!(
+                e.help().unwrap().to_string(),
+                "did you mean `fails`? available tests: passes, fails, ignored"
+            ),
+            _ => panic!("expected an unknown test"),
+        }
+    }
+
+    #[test]
+    fn test_races() {
+        let src = "
+        circuit add: a b c_in -> o c_out {
+            o = a xor b xor c_in;
+            c_out = (a and b) or ((a xor b) and c_in);
+        }
+
+        test adds {
+            o, c_out = add(a, b, 1);
+
+            at 0ns:
+                a = 1;
+                b = 0;
+                assert c_out == 1;
+                assert o == 0;
+        }
+
+        test racy {
+            at 0ns:
+                a = 1;
+                a = 0;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        for shuffle_seed in [None, Some(3)] {
+            let config = SimulationConfig {
+                detect_races: true,
+                shuffle_seed,
+                ..SimulationConfig::default()
+            };
+            let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+                .nice_unwrap_panic();
+
+            s.run_test("adds")
+                .and_then(TestReport::into_result)
+                .nice_unwrap_panic();
+            match s.run_test("racy").and_then(TestReport::into_result) {
+                Err(SimulationError::RaceDetected(e)) => assert_eq!(
+                    e.help().unwrap().to_string(),
+                    "with those events in reverse order, these signals ended with a \
+                    different value: racy.a. signals should have one driver at a time"
+                ),
+                other => panic!("expected a race, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_covers() {
+        let src = "
+        circuit and2: a b -> o {
+            o = a and b;
+            cover both: a and b;
+            cover neither: a nor b;
+        }
+
+        test main {
+            o = and2(a, b);
+            p = and2(b, a);
+            cover o;
+
+            at 0ns:
+                a = 1;
+                b = 0;
+            at 1ns:
+                b = 1;
+            at 2ns:
+                a = 0;
+            at 3ns:
+                a = 1;
+                assert p == 1;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        for engine in [Engine::EventDriven, Engine::Compiled] {
+            let config = SimulationConfig {
+                engine,
+                ..SimulationConfig::default()
+            };
+            let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+                .nice_unwrap_panic();
+
+            let report = s.run_test("main").nice_unwrap_panic();
+            let table = CoverTable::new(&[report]);
+
+            let hits = |name: &str| {
+                let row = table
+                    .rows()
+                    .iter()
+                    .find(|i| i.bin().name() == name)
+                    .unwrap();
+                (row.hits(), row.first().map(|(_, t)| t.nanos()))
+            };
+            // both instances count towards the same bins
+            assert_eq!(hits("both"), (4, Some(1)));
+            assert_eq!(hits("neither"), (0, None));
+            assert_eq!(hits("o"), (2, Some(1)));
+
+            let never: Vec<_> = table.never_hit().iter().map(|i| i.bin().name()).collect();
+            assert_eq!(never, vec!["neither"]);
+            assert!(table
+                .to_string()
+                .ends_with("neither       0  never\no             2  main at 1ns\ncover result: 2 of 3 bins hit"));
+        }
+    }
 }
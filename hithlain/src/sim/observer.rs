@@ -0,0 +1,229 @@
+use crate::parse::span::Span;
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::linked_ast::Statement;
+use crate::sim::value::Value;
+use crate::sim::SimulationError;
+use crate::time::Instant;
+use std::sync::{Arc, Weak};
+
+/// Receives everything that happens during a simulation. Every method does nothing
+/// by default, so an observer only implements what it's interested in.
+pub trait SimulationObserver {
+    /// Called once before the simulation starts, with every signal. That includes
+    /// the ones generated by the compiler, for the parts of expressions.
+    fn start(&mut self, _signals: &[UniqueVariableRef]) {}
+
+    /// Called once after [`SimulationObserver::start`], with every statement of the process,
+    /// to see how signals are connected.
+    fn netlist(&mut self, _statements: &[Arc<Statement>]) {}
+
+    /// A variable got a new value.
+    fn value_changed(
+        &mut self,
+        _variable: &UniqueVariableRef,
+        _value: &Value,
+        _time: Instant,
+    ) -> Result<(), SimulationError> {
+        Ok(())
+    }
+
+    /// A statement ran, either as an event or as part of the compiled netlist.
+    fn statement_evaluated(&mut self, _statement: &Statement, _time: Instant) {}
+
+    /// An assertion was checked. Temporal assertions are reported when they fail,
+    /// or when their window ends without failing.
+    fn assertion(&mut self, _span: &Span, _passed: bool, _time: Instant) {}
+
+    /// The simulation moved on to a later nanosecond.
+    fn time_advanced(&mut self, _from: Instant, _to: Instant) {}
+
+    /// The simulation ended, with its last event at `time`.
+    fn finish(&mut self, _time: Instant) -> Result<(), SimulationError> {
+        Ok(())
+    }
+
+    /// Called when a snapshot is taken. The snapshot keeps the checkpoint until it is dropped,
+    /// and passes its position to [`SimulationObserver::rewind`] when it is restored.
+    fn checkpoint(&mut self) -> Checkpoint {
+        Checkpoint::default()
+    }
+
+    /// Forgets everything observed since the checkpoint. Snapshots loaded
+    /// from a file rewind to 0.
+    fn rewind(&mut self, _checkpoint: usize) {}
+}
+
+/// Where an observer was when a snapshot was taken. Snapshots (and their clones) hold on to it,
+/// so an observer can tell with [`Checkpoint::watch`] whether it may still have to rewind.
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoint {
+    position: usize,
+    alive: Arc<()>,
+}
+
+impl Checkpoint {
+    #[must_use]
+    pub fn new(position: usize) -> Self {
+        Self {
+            position,
+            alive: Arc::new(()),
+        }
+    }
+
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Can't be upgraded anymore once every snapshot with this checkpoint is dropped.
+    #[must_use]
+    pub fn watch(&self) -> Weak<()> {
+        Arc::downgrade(&self.alive)
+    }
+}
+
+/// Creates an observer for every simulation, so tests running in parallel each get their own.
+/// Observers that collect something across tests can share state through an `Arc<Mutex<_>>`.
+pub trait ObserverFactory: Send + Sync {
+    /// `name` is the name of the test, check or circuit being simulated.
+    fn create(&self, name: &str) -> Box<dyn SimulationObserver>;
+}
+
+impl<F, O> ObserverFactory for F
+where
+    F: Fn(&str) -> O + Send + Sync,
+    O: SimulationObserver + 'static,
+{
+    fn create(&self, name: &str) -> Box<dyn SimulationObserver> {
+        Box::new(self(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SimulationObserver;
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::parse::span::Span;
+    use crate::sim::config::{Engine, SimulationConfig};
+    use crate::sim::instantiate::UniqueVariableRef;
+    use crate::sim::linked_ast::Statement;
+    use crate::sim::value::Value;
+    use crate::sim::{SimulationError, Simulator};
+    use crate::time::Instant;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Log {
+        changes: Vec<String>,
+        statements: usize,
+        assertions: Vec<(u64, bool)>,
+        advances: Vec<u64>,
+        finished: Option<u64>,
+    }
+
+    struct Logger(Arc<Mutex<Log>>);
+
+    impl SimulationObserver for Logger {
+        fn value_changed(
+            &mut self,
+            variable: &UniqueVariableRef,
+            value: &Value,
+            time: Instant,
+        ) -> Result<(), SimulationError> {
+            if !variable.generated {
+                let Value::Bit(b) = value;
+                self.0.lock().unwrap().changes.push(format!(
+                    "{}ns {}={}",
+                    time.nanos(),
+                    variable.name(),
+                    u8::from(*b)
+                ));
+            }
+            Ok(())
+        }
+
+        fn statement_evaluated(&mut self, _statement: &Statement, _time: Instant) {
+            self.0.lock().unwrap().statements += 1;
+        }
+
+        fn assertion(&mut self, _span: &Span, passed: bool, time: Instant) {
+            self.0
+                .lock()
+                .unwrap()
+                .assertions
+                .push((time.nanos(), passed));
+        }
+
+        fn time_advanced(&mut self, _from: Instant, to: Instant) {
+            self.0.lock().unwrap().advances.push(to.nanos());
+        }
+
+        fn finish(&mut self, time: Instant) -> Result<(), SimulationError> {
+            self.0.lock().unwrap().finished = Some(time.nanos());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn observe() {
+        let src = "
+        circuit inv: a -> o {
+            o = not(a);
+        }
+
+        test main {
+            o = inv(a);
+
+            at 0ns:
+                a = 1;
+                assert o == 0;
+            at 5ns:
+                a = 0;
+                assert o == 0;
+                assert always (o == 1) from 5ns to 8ns;
+        }
+        ";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        for engine in [Engine::EventDriven, Engine::Compiled] {
+            let log = Arc::new(Mutex::new(Log::default()));
+            let shared = log.clone();
+
+            let config = SimulationConfig {
+                engine,
+                observers: vec![Arc::new(move |_: &str| Logger(shared.clone()))],
+                ..SimulationConfig::default()
+            };
+            let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+                .nice_unwrap_panic();
+            assert!(s.run_test("main").nice_unwrap_panic().failure().is_some());
+
+            let log = log.lock().unwrap();
+            assert_eq!(
+                log.changes,
+                vec![
+                    "0ns main.a=1",
+                    "0ns main.inv.a=1",
+                    "0ns main.inv.o=0",
+                    "0ns main.o=0",
+                    "5ns main.a=0",
+                    "5ns main.inv.a=0",
+                    "5ns main.inv.o=1",
+                    "5ns main.o=1",
+                ]
+            );
+            assert!(log.statements > 0);
+            assert_eq!(log.assertions, vec![(0, true), (5, false), (8, true)]);
+            assert_eq!(log.advances, vec![5, 8]);
+            assert_eq!(log.finished, Some(8));
+        }
+    }
+}
@@ -0,0 +1,581 @@
+use crate::analyze::stats::Readers;
+use crate::parse::span::Span;
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::instantiated_ast::Package;
+use crate::sim::linked_ast::Statement;
+use crate::sim::observer::{ObserverFactory, SimulationObserver};
+use crate::sim::value::Value;
+use crate::sim::SimulationError;
+use crate::time::Instant;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// How many nets the report shows, the ones that used the most energy.
+const BUSIEST_NETS: usize = 5;
+
+/// What dynamic power is estimated with. Every toggle of a net takes `C * V^2 / 2`,
+/// so with femtofarads and volts, energy is in femtojoules and power in microwatts.
+#[derive(Debug, Clone)]
+pub struct PowerModel {
+    /// the supply voltage, in volts
+    pub voltage: f64,
+    /// the capacitance of every net, in femtofarads
+    pub capacitance: f64,
+    /// the capacitance every gate input a net drives adds to it, in femtofarads
+    pub load: f64,
+    /// the capacitance of single nets instead, by name without the test, like `add.c_out`
+    pub nets: HashMap<String, f64>,
+}
+
+impl Default for PowerModel {
+    fn default() -> Self {
+        Self {
+            voltage: 1.0,
+            capacitance: 1.0,
+            load: 1.0,
+            nets: HashMap::new(),
+        }
+    }
+}
+
+impl PowerModel {
+    fn capacitance(&self, name: Option<&str>, fan_out: usize) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        name.and_then(|i| self.nets.get(i))
+            .copied()
+            .unwrap_or(self.capacitance + self.load * fan_out as f64)
+    }
+}
+
+/// One net in one instance: a signal, and every signal it is moved to.
+#[derive(Debug, Clone)]
+pub struct NetActivity {
+    /// the signal that drives it, `None` for the parts of an expression
+    pub(crate) signal: Option<String>,
+    /// the expression that drives it, if any
+    pub(crate) span: Option<Span>,
+    pub(crate) instance: String,
+    /// the number of gate inputs it drives, through every instance
+    pub(crate) fan_out: usize,
+    /// in femtofarads
+    pub(crate) capacitance: f64,
+    pub(crate) toggles: usize,
+}
+
+impl NetActivity {
+    #[must_use]
+    pub fn signal(&self) -> Option<&str> {
+        self.signal.as_deref()
+    }
+
+    #[must_use]
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    #[must_use]
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    #[must_use]
+    pub fn fan_out(&self) -> usize {
+        self.fan_out
+    }
+
+    #[must_use]
+    pub fn capacitance(&self) -> f64 {
+        self.capacitance
+    }
+
+    #[must_use]
+    pub fn toggles(&self) -> usize {
+        self.toggles
+    }
+
+    /// The energy all of its toggles took, in femtojoules.
+    #[must_use]
+    pub fn energy(&self, voltage: f64) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let toggles = self.toggles as f64;
+        toggles * self.capacitance * voltage * voltage / 2.0
+    }
+}
+
+impl Display for NetActivity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.signal, &self.span) {
+            (Some(signal), _) => write!(f, "{}", signal),
+            (None, Some(span)) => write!(
+                f,
+                "{} ({}:{})",
+                span.text(),
+                span.source().name(),
+                span.line()
+            ),
+            (None, None) => write!(f, "{}.?", self.instance),
+        }
+    }
+}
+
+/// The nets of one instance, without the instances in it.
+#[derive(Debug, Clone)]
+pub struct InstanceActivity {
+    pub(crate) instance: String,
+    pub(crate) toggles: usize,
+    /// in femtojoules
+    pub(crate) energy: f64,
+}
+
+impl InstanceActivity {
+    #[must_use]
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    #[must_use]
+    pub fn toggles(&self) -> usize {
+        self.toggles
+    }
+
+    #[must_use]
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+}
+
+/// The switching activity of the circuits in one test, and the dynamic power it takes.
+#[derive(Debug, Clone)]
+pub struct TestPower {
+    pub(crate) test: String,
+    /// the top level circuits
+    pub(crate) circuits: Vec<String>,
+    pub(crate) voltage: f64,
+    /// the last instant anything happened at
+    pub(crate) simulated: u64,
+    pub(crate) nets: Vec<NetActivity>,
+}
+
+impl TestPower {
+    #[must_use]
+    pub fn test(&self) -> &str {
+        &self.test
+    }
+
+    #[must_use]
+    pub fn circuits(&self) -> &[String] {
+        &self.circuits
+    }
+
+    /// The simulated interval, in nanoseconds.
+    #[must_use]
+    pub fn simulated(&self) -> u64 {
+        self.simulated
+    }
+
+    /// Every net in a circuit, in the order they were created.
+    #[must_use]
+    pub fn nets(&self) -> &[NetActivity] {
+        &self.nets
+    }
+
+    /// Every instance with at least one net, in the order they were created.
+    #[must_use]
+    pub fn instances(&self) -> Vec<InstanceActivity> {
+        let mut res: Vec<InstanceActivity> = Vec::new();
+        for i in &self.nets {
+            let index = match res.iter().position(|r| r.instance == i.instance) {
+                Some(index) => index,
+                None => {
+                    res.push(InstanceActivity {
+                        instance: i.instance.clone(),
+                        toggles: 0,
+                        energy: 0.0,
+                    });
+                    res.len() - 1
+                }
+            };
+
+            res[index].toggles += i.toggles;
+            res[index].energy += i.energy(self.voltage);
+        }
+        res
+    }
+
+    #[must_use]
+    pub fn toggles(&self) -> usize {
+        self.nets.iter().map(|i| i.toggles).sum()
+    }
+
+    /// In femtojoules.
+    #[must_use]
+    pub fn energy(&self) -> f64 {
+        self.nets.iter().map(|i| i.energy(self.voltage)).sum()
+    }
+
+    /// The average dynamic power over the simulated interval, in microwatts.
+    #[must_use]
+    pub fn power(&self) -> f64 {
+        self.power_of(self.energy())
+    }
+
+    fn power_of(&self, energy: f64) -> f64 {
+        if self.simulated == 0 {
+            0.0
+        } else {
+            #[allow(clippy::cast_precision_loss)]
+            let nanos = self.simulated as f64;
+            energy / nanos
+        }
+    }
+}
+
+impl Display for TestPower {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "power of {} in {} over {}ns at {}V:",
+            self.circuits.join(", "),
+            self.test,
+            self.simulated,
+            self.voltage
+        )?;
+
+        let instances = self.instances();
+        let width = instances
+            .iter()
+            .map(|i| i.instance.len())
+            .chain(std::iter::once("instance".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "  {:width$}  {:>8}  {:>12}  {:>11}",
+            "instance",
+            "toggles",
+            "energy (fJ)",
+            "power (uW)",
+            width = width
+        )?;
+        for i in &instances {
+            writeln!(
+                f,
+                "  {:width$}  {:>8}  {:>12.2}  {:>11.2}",
+                i.instance,
+                i.toggles,
+                i.energy,
+                self.power_of(i.energy),
+                width = width
+            )?;
+        }
+        writeln!(
+            f,
+            "  {:width$}  {:>8}  {:>12.2}  {:>11.2}",
+            "total",
+            self.toggles(),
+            self.energy(),
+            self.power(),
+            width = width
+        )?;
+
+        let mut busiest: Vec<_> = self.nets.iter().filter(|i| i.toggles > 0).collect();
+        busiest.sort_by(|a, b| b.energy(self.voltage).total_cmp(&a.energy(self.voltage)));
+        busiest.truncate(BUSIEST_NETS);
+        if !busiest.is_empty() {
+            writeln!(f, "  busiest nets:")?;
+        }
+        for i in busiest {
+            writeln!(
+                f,
+                "    {}: {} toggle(s), fan-out {}, {}fF",
+                i, i.toggles, i.fan_out, i.capacitance
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts how often every net in a circuit toggles, in every simulation it is registered for,
+/// and estimates the dynamic power that takes. Glitches are toggles too. Clones share their results.
+#[derive(Debug, Clone, Default)]
+pub struct PowerEstimate {
+    model: Arc<PowerModel>,
+    /// by test
+    tests: Arc<Mutex<BTreeMap<String, TestPower>>>,
+}
+
+impl PowerEstimate {
+    #[must_use]
+    pub fn new(model: PowerModel) -> Self {
+        Self {
+            model: Arc::new(model),
+            tests: Arc::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, TestPower>> {
+        self.tests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Every simulation so far that had a circuit in it, sorted by name.
+    #[must_use]
+    pub fn tests(&self) -> Vec<TestPower> {
+        self.lock().values().cloned().collect()
+    }
+}
+
+impl ObserverFactory for PowerEstimate {
+    fn create(&self, name: &str) -> Box<dyn SimulationObserver> {
+        Box::new(PowerObserver {
+            estimate: self.clone(),
+            test: name.to_string(),
+            nets: HashMap::new(),
+            initialized: HashSet::new(),
+        })
+    }
+}
+
+/// The signal a statement moves another signal from.
+fn moved_from(statement: &Statement) -> Option<&UniqueVariableRef> {
+    match statement {
+        Statement::Move(_, from) => Some(from),
+        Statement::Delayed(s, _) => moved_from(s),
+        _ => None,
+    }
+}
+
+struct PowerObserver {
+    estimate: PowerEstimate,
+    test: String,
+    /// the index of the net every signal that drives one is
+    nets: HashMap<usize, usize>,
+    /// signals that got their first value, which isn't a toggle yet
+    initialized: HashSet<usize>,
+}
+
+impl SimulationObserver for PowerObserver {
+    #[allow(clippy::mutable_key_type)]
+    fn netlist(&mut self, statements: &[Arc<Statement>]) {
+        let mut readers = Readers::default();
+        let mut sources = HashMap::new();
+        let mut signals = BTreeMap::new();
+
+        for i in statements {
+            if let Some(v) = i.writes() {
+                signals.insert(v.identifier, v.clone());
+            }
+
+            match (moved_from(i), i.writes()) {
+                (Some(from), Some(into)) => {
+                    readers.read(from, Some(into));
+                    sources.insert(into.clone(), from.clone());
+                }
+                // neither are assertions, covers and the gates that compare values in a test
+                (None, Some(into)) if into.in_circuit() => {
+                    for v in i.reads() {
+                        readers.read(v, None);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut circuits = Vec::new();
+        let mut instances: HashMap<*const Vec<Package>, String> = HashMap::new();
+        // how many instances there are of every path
+        let mut same_path: HashMap<String, usize> = HashMap::new();
+        let mut nets = Vec::new();
+        for v in signals.into_values() {
+            // a signal moved from another one in a circuit is part of the same net.
+            // the inputs of the top level are driven from the test, so they are nets of their own
+            if !v.in_circuit() || sources.get(&v).is_some_and(UniqueVariableRef::in_circuit) {
+                continue;
+            }
+
+            let names: Vec<_> = v
+                .original
+                .path
+                .iter()
+                .skip(1)
+                .map(|i| i.name().0.as_str())
+                .collect();
+            let path = names.join(".");
+
+            let instance = instances
+                .entry(Arc::as_ptr(&v.original.path))
+                .or_insert_with(|| {
+                    // instances of the same circuit share names, so they are numbered
+                    let same = same_path.entry(path.clone()).or_insert(0);
+                    *same += 1;
+                    let name = if *same > 1 {
+                        format!("{} (instance {})", path, same)
+                    } else {
+                        path.clone()
+                    };
+                    if names.len() == 1 {
+                        circuits.push(name.clone());
+                    }
+                    name
+                })
+                .clone();
+
+            let signal = (!v.generated).then(|| format!("{}.{}", path, v.original.variable.0));
+            let fan_out = readers.fan_out(&v);
+
+            self.nets.insert(v.identifier, nets.len());
+            nets.push(NetActivity {
+                capacitance: self.estimate.model.capacitance(signal.as_deref(), fan_out),
+                signal,
+                span: v.gate().cloned(),
+                instance,
+                fan_out,
+                toggles: 0,
+            });
+        }
+
+        if nets.is_empty() {
+            return;
+        }
+        self.estimate.lock().insert(
+            self.test.clone(),
+            TestPower {
+                test: self.test.clone(),
+                circuits,
+                voltage: self.estimate.model.voltage,
+                simulated: 0,
+                nets,
+            },
+        );
+    }
+
+    fn value_changed(
+        &mut self,
+        variable: &UniqueVariableRef,
+        _value: &Value,
+        time: Instant,
+    ) -> Result<(), SimulationError> {
+        let index = match self.nets.get(&variable.identifier) {
+            Some(index) => *index,
+            None => return Ok(()),
+        };
+        if self.initialized.insert(variable.identifier) {
+            return Ok(());
+        }
+
+        if let Some(test) = self.estimate.lock().get_mut(&self.test) {
+            test.nets[index].toggles += 1;
+            test.simulated = test.simulated.max(time.nanos());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, time: Instant) -> Result<(), SimulationError> {
+        if let Some(test) = self.estimate.lock().get_mut(&self.test) {
+            test.simulated = test.simulated.max(time.nanos());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PowerEstimate, PowerModel};
+    use crate::error::NiceUnwrap;
+    use crate::parse::desugar::desugar_program;
+    use crate::parse::lexer::lex;
+    use crate::parse::parser::Parser;
+    use crate::parse::source::Source;
+    use crate::sim::config::{Engine, SimulationConfig};
+    use crate::sim::Simulator;
+    use std::sync::Arc;
+
+    #[test]
+    fn switching_activity() {
+        let src = "
+circuit buf: a -> o {
+    o = a and a;
+}
+
+circuit two: a b -> o p {
+    o = buf(a);
+    p = buf(b);
+}
+
+test main {
+    o, p = two(a, b);
+
+    at 0ns:
+        a = 0;
+        b = 0;
+    at 1ns:
+        a = 1;
+    at 2ns:
+        a = 0;
+        b = 1;
+    at 4ns:
+        assert o == 0;
+        assert p == 1;
+}
+";
+
+        let lexed = lex(&Source::test(src)).nice_unwrap_panic();
+        let mut parser = Parser::new(lexed);
+        let parsed = parser.parse_program().nice_unwrap_panic();
+
+        let mut model = PowerModel::default();
+        model.nets.insert("two.buf.o".to_string(), 10.0);
+        let estimate = PowerEstimate::new(model);
+        let config = SimulationConfig {
+            engine: Engine::Compiled,
+            observers: vec![Arc::new(estimate.clone())],
+            ..SimulationConfig::default()
+        };
+        let s = Simulator::new(desugar_program(&parsed).nice_unwrap_panic(), config)
+            .nice_unwrap_panic();
+        s.run_test("main")
+            .nice_unwrap_panic()
+            .into_result()
+            .nice_unwrap_panic();
+
+        let tests = estimate.tests();
+        assert_eq!(tests.len(), 1);
+        let power = &tests[0];
+        assert_eq!(power.circuits(), ["two"]);
+        assert_eq!(power.simulated(), 4);
+
+        // the inputs of the top level are nets, the wires into and out of `buf` aren't
+        let nets: Vec<_> = power
+            .nets()
+            .iter()
+            .map(|i| (i.to_string(), i.fan_out(), i.toggles()))
+            .collect();
+        assert_eq!(
+            nets,
+            vec![
+                ("two.a".to_string(), 2, 2),
+                ("two.b".to_string(), 2, 1),
+                ("two.buf.o".to_string(), 0, 2),
+                ("two.buf.o".to_string(), 0, 1),
+            ]
+        );
+
+        let instances: Vec<_> = power
+            .instances()
+            .iter()
+            .map(|i| (i.instance().to_string(), i.toggles(), i.energy()))
+            .collect();
+        assert_eq!(
+            instances,
+            vec![
+                ("two".to_string(), 3, 4.5),
+                ("two.buf".to_string(), 2, 10.0),
+                ("two.buf (instance 2)".to_string(), 1, 5.0),
+            ]
+        );
+        assert_eq!(power.energy(), 19.5);
+        assert_eq!(power.power(), 19.5 / 4.0);
+    }
+}
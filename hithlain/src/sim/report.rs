@@ -0,0 +1,308 @@
+use crate::parse::ast::CoverBin;
+use crate::sim::{SimulationError, TimeLimitReached};
+use crate::time::{Duration, Instant};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    /// ignored, or not run because an earlier test failed with `fail_fast`
+    Skipped,
+}
+
+impl Display for TestStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestStatus::Passed => write!(f, "ok"),
+            TestStatus::Failed => write!(f, "FAILED"),
+            TestStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// When the condition of one `cover` statement became true during a test.
+/// Instances of the same circuit share their bins.
+#[derive(Debug, Clone)]
+pub struct CoverHits {
+    pub(crate) bin: CoverBin,
+    pub(crate) times: Vec<Instant>,
+}
+
+impl CoverHits {
+    pub(crate) fn new(bin: CoverBin) -> Self {
+        Self {
+            bin,
+            times: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn bin(&self) -> &CoverBin {
+        &self.bin
+    }
+
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        self.times.len()
+    }
+
+    #[must_use]
+    pub fn times(&self) -> &[Instant] {
+        &self.times
+    }
+}
+
+/// What happened when running a single test or check.
+#[derive(Debug)]
+pub struct TestReport {
+    pub(crate) name: String,
+    pub(crate) status: TestStatus,
+    /// the number of assertions that were checked
+    pub(crate) assertions: usize,
+    /// the time of the last event that was simulated
+    pub(crate) simulated: Duration,
+    pub(crate) wall_time: std::time::Duration,
+    pub(crate) covers: Vec<CoverHits>,
+
+    pub(crate) failure: Option<SimulationError>,
+    pub(crate) warnings: Vec<TimeLimitReached>,
+}
+
+impl TestReport {
+    pub(crate) fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: TestStatus::Skipped,
+            assertions: 0,
+            simulated: Duration::from_nanos(0),
+            wall_time: std::time::Duration::ZERO,
+            covers: Vec::new(),
+            failure: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub(crate) fn finish(&mut self, res: Result<(), SimulationError>) {
+        match res {
+            Ok(()) => self.status = TestStatus::Passed,
+            Err(e) => {
+                self.status = TestStatus::Failed;
+                self.failure = Some(e);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn status(&self) -> TestStatus {
+        self.status
+    }
+
+    #[must_use]
+    pub fn assertions(&self) -> usize {
+        self.assertions
+    }
+
+    #[must_use]
+    pub fn simulated(&self) -> Duration {
+        self.simulated
+    }
+
+    #[must_use]
+    pub fn wall_time(&self) -> std::time::Duration {
+        self.wall_time
+    }
+
+    /// Every `cover` statement in the test and the circuits it uses.
+    #[must_use]
+    pub fn covers(&self) -> &[CoverHits] {
+        &self.covers
+    }
+
+    /// Why the test failed, if it did.
+    #[must_use]
+    pub fn failure(&self) -> Option<&SimulationError> {
+        self.failure.as_ref()
+    }
+
+    #[must_use]
+    pub fn warnings(&self) -> &[TimeLimitReached] {
+        &self.warnings
+    }
+
+    /// The failure as an error, for when only passing or failing matters.
+    pub fn into_result(self) -> Result<(), SimulationError> {
+        self.failure.map_or(Ok(()), Err)
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ... {}", self.name, self.status)?;
+        if self.status != TestStatus::Skipped {
+            write!(
+                f,
+                " ({} assertion(s), {}ns simulated in {:.2?})",
+                self.assertions,
+                self.simulated.nanos(),
+                self.wall_time
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// How many of a set of tests passed, failed or were skipped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl Summary {
+    #[must_use]
+    pub fn new(reports: &[TestReport]) -> Self {
+        let count = |status| reports.iter().filter(|i| i.status == status).count();
+
+        Self {
+            passed: count(TestStatus::Passed),
+            failed: count(TestStatus::Failed),
+            skipped: count(TestStatus::Skipped),
+        }
+    }
+
+    #[must_use]
+    pub fn success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "test result: {}. {} passed; {} failed; {} skipped",
+            if self.success() { "ok" } else { "FAILED" },
+            self.passed,
+            self.failed,
+            self.skipped
+        )
+    }
+}
+
+/// One row of a [`CoverTable`].
+#[derive(Debug, Clone)]
+pub struct CoverRow {
+    pub(crate) bin: CoverBin,
+    pub(crate) hits: usize,
+    /// the test it was first hit in, and when
+    pub(crate) first: Option<(String, Instant)>,
+}
+
+impl CoverRow {
+    #[must_use]
+    pub fn bin(&self) -> &CoverBin {
+        &self.bin
+    }
+
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    #[must_use]
+    pub fn first(&self) -> Option<&(String, Instant)> {
+        self.first.as_ref()
+    }
+}
+
+/// How often every `cover` statement was hit, added up over a set of tests.
+#[derive(Debug, Clone)]
+pub struct CoverTable {
+    pub(crate) rows: Vec<CoverRow>,
+}
+
+impl CoverTable {
+    #[must_use]
+    pub fn new(reports: &[TestReport]) -> Self {
+        let mut rows: Vec<CoverRow> = Vec::new();
+
+        for report in reports {
+            for i in &report.covers {
+                let row = if let Some(row) = rows.iter_mut().find(|row| row.bin == i.bin) {
+                    row
+                } else {
+                    rows.push(CoverRow {
+                        bin: i.bin.clone(),
+                        hits: 0,
+                        first: None,
+                    });
+                    rows.last_mut().expect("just pushed")
+                };
+
+                row.hits += i.hits();
+                if row.first.is_none() {
+                    row.first = i.times.first().map(|t| (report.name.clone(), *t));
+                }
+            }
+        }
+
+        Self { rows }
+    }
+
+    #[must_use]
+    pub fn rows(&self) -> &[CoverRow] {
+        &self.rows
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The bins that were never hit in any of the tests.
+    #[must_use]
+    pub fn never_hit(&self) -> Vec<&CoverRow> {
+        self.rows.iter().filter(|i| i.hits == 0).collect()
+    }
+}
+
+impl Display for CoverTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .rows
+            .iter()
+            .map(|i| i.bin.name.len())
+            .chain(std::iter::once("cover".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:width$}  {:>6}  first hit",
+            "cover",
+            "hits",
+            width = width
+        )?;
+        for i in &self.rows {
+            write!(f, "{:width$}  {:>6}  ", i.bin.name, i.hits, width = width)?;
+            match &i.first {
+                Some((test, time)) => writeln!(f, "{} at {}ns", test, time.nanos())?,
+                None => writeln!(f, "never")?,
+            }
+        }
+
+        write!(
+            f,
+            "cover result: {} of {} bins hit",
+            self.rows.len() - self.never_hit().len(),
+            self.rows.len()
+        )
+    }
+}
@@ -1,22 +1,25 @@
 use crate::sim::linked_ast::Statement;
 use crate::time::Instant;
 use std::cmp::Ordering;
-use std::rc::Rc;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct Signal {
     pub(crate) time: Instant,
-    pub(crate) action: Rc<Statement>,
+    /// breaks ties between signals at the same time, see [`EventOrder`]
+    pub(crate) sequence: u64,
+    pub(crate) action: Arc<Statement>,
 }
 
 impl PartialEq<Self> for Signal {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
+        self.time == other.time && self.sequence == other.sequence
     }
 }
 
 impl PartialOrd for Signal {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.time.partial_cmp(&other.time)
+        Some(self.cmp(other))
     }
 }
 
@@ -24,6 +27,61 @@ impl Eq for Signal {}
 
 impl Ord for Signal {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.time.cmp(&other.time)
+        self.time
+            .cmp(&other.time)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Tiebreak {
+    Scheduled,
+    Reversed,
+    Shuffled(u64),
+}
+
+/// Hands out the sequence numbers of signals. By default signals at the same time run in the
+/// order they were scheduled in. They can also run in reverse, or in a random (but repeatable)
+/// order generated from a seed, to find designs and tests that depend on that order.
+#[derive(Clone, Copy)]
+pub struct EventOrder {
+    tiebreak: Tiebreak,
+    pub(crate) scheduled: u64,
+}
+
+impl EventOrder {
+    /// In scheduling order, or shuffled when there's a seed.
+    #[must_use]
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            tiebreak: seed.map_or(Tiebreak::Scheduled, Tiebreak::Shuffled),
+            scheduled: 0,
+        }
+    }
+
+    /// The last signal scheduled for a time runs first.
+    #[must_use]
+    pub fn reversed() -> Self {
+        Self {
+            tiebreak: Tiebreak::Reversed,
+            scheduled: 0,
+        }
+    }
+
+    /// The sequence number of the next signal that is scheduled.
+    pub fn sequence(&mut self) -> u64 {
+        self.scheduled += 1;
+
+        match self.tiebreak {
+            Tiebreak::Scheduled => self.scheduled,
+            Tiebreak::Reversed => u64::MAX - self.scheduled,
+            // splitmix64, so every sequence number only depends on the seed and how many came before
+            Tiebreak::Shuffled(seed) => {
+                let mut z = seed.wrapping_add(self.scheduled.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^ (z >> 31)
+            }
+        }
     }
 }
@@ -1,14 +1,19 @@
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap};
-use std::rc::Rc;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
 
 use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 
-use crate::sim::config::SimulationConfig;
+use crate::parse::span::Span;
+use crate::sim::compiled::{is_combinational, Netlist};
+use crate::sim::config::{DelayMode, Engine, SimulationConfig};
 use crate::sim::instantiate::UniqueVariableRef;
-use crate::sim::linked_ast::{BinaryBuiltin, Condition, Process, Statement};
-use crate::sim::signal::Signal;
+use crate::sim::linked_ast::{BinaryBuiltin, Condition, Process, Statement, WindowKind};
+use crate::sim::observer::{Checkpoint, SimulationObserver};
+use crate::sim::report::CoverHits;
+use crate::sim::signal::{EventOrder, Signal};
+use crate::sim::snapshot::{Snapshot, SnapshotTable};
 use crate::sim::value::Value;
 use crate::sim::SimulationError;
 use crate::time::Instant;
@@ -16,22 +21,76 @@ use crate::vcd::vcd_ast::VcdModule;
 use crate::vcd::VcdGenerator;
 
 #[derive(Error, Debug, Diagnostic)]
-#[error("assertion failed")]
+#[error("assertion failed at {}ns", time.nanos())]
 #[diagnostic()]
 pub struct AssertionError {
     #[source_code]
-    src: NamedSource,
+    pub(crate) src: NamedSource,
 
-    #[label("here")]
-    span: SourceSpan,
+    #[label("{}", reason)]
+    pub(crate) span: SourceSpan,
+
+    pub(crate) time: Instant,
+    pub(crate) reason: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("signals kept changing for {} delta cycles at {}ns", time.deltas(), time.nanos())]
+#[diagnostic(help("oscillating signals: {}", signals))]
+pub struct Oscillation {
+    time: Instant,
+    signals: String,
+}
+
+/// The state of a temporal assertion while it is being watched.
+struct Window {
+    variable: UniqueVariableRef,
+    kind: WindowKind,
+    span: Span,
+
+    state: WindowState,
+}
+
+/// The part of a window that changes during the simulation.
+#[derive(Clone, Default)]
+pub(crate) struct WindowState {
+    pub(crate) active: bool,
+    pub(crate) start_value: Option<Value>,
+    pub(crate) satisfied: bool,
 }
 
 pub struct Simulation<'config> {
+    /// every statement of the process, in the order it was linked
+    statements: Vec<Arc<Statement>>,
+
     pq: BinaryHeap<Reverse<Signal>>,
-    map: HashMap<UniqueVariableRef, Vec<Rc<Statement>>>,
+    order: EventOrder,
+    map: HashMap<UniqueVariableRef, Vec<Arc<Statement>>>,
+    /// the combinational statements, when using the compiled engine
+    netlist: Option<Netlist>,
     store: HashMap<UniqueVariableRef, Value>,
+    /// variables that keep their value, regardless of what drives them
+    forced: HashSet<UniqueVariableRef>,
+
+    windows: Vec<Window>,
+    watched: HashMap<UniqueVariableRef, Vec<Arc<Statement>>>,
 
-    vcd: Option<VcdGenerator>,
+    /// the vcd writer (if any) and the observers from the config
+    observers: Vec<Box<dyn SimulationObserver>>,
+
+    /// failed assertions, when not failing fast
+    failures: Vec<AssertionError>,
+    /// the number of assertions checked so far
+    assertions: usize,
+
+    /// every distinct cover statement, and when it was hit
+    covers: Vec<CoverHits>,
+    /// whether the condition of a cover was true when it was last checked
+    covered: HashMap<UniqueVariableRef, bool>,
+
+    /// the generation and value of the last write scheduled for a variable by a delayed statement
+    pending: HashMap<UniqueVariableRef, (u64, Value)>,
+    generation: u64,
 
     config: &'config SimulationConfig,
 
@@ -43,86 +102,307 @@ impl<'config> Simulation<'config> {
         process: Process,
         config: &'config SimulationConfig,
         vcd_ast: Option<VcdModule>,
+    ) -> Result<Self, SimulationError> {
+        Self::with_event_order(
+            process,
+            config,
+            vcd_ast,
+            EventOrder::new(config.shuffle_seed),
+        )
+    }
+
+    /// Like [`Simulation::new`], but with a different order for events at the same time
+    /// than the one in the config.
+    #[allow(clippy::mutable_key_type)]
+    pub fn with_event_order(
+        process: Process,
+        config: &'config SimulationConfig,
+        vcd_ast: Option<VcdModule>,
+        mut order: EventOrder,
     ) -> Result<Self, SimulationError> {
         let mut map = HashMap::new();
         let mut pq = BinaryHeap::new();
+        let mut windows = Vec::new();
+        let mut watched = HashMap::new();
+        let mut covers = Vec::new();
+        let mut statements = Vec::new();
+        let mut compiled = Vec::new();
+        let mut compiled_seen = HashSet::new();
 
         let max_time = Instant::START;
 
         for i in process.conditions {
             match i {
                 Condition::AtTime { time, run } => {
+                    statements.push(run.clone());
                     // println!("{:?} --> {:?}", time, run);
                     pq.push(Reverse(Signal {
                         time,
+                        sequence: order.sequence(),
                         action: run.clone(),
                     }));
                 }
                 Condition::WhenChanges { variable, run } => {
+                    statements.push(run.clone());
+
+                    // like temporal assertions, covers only see the settled value
+                    if let Statement::Cover(_, bin) = &*run {
+                        if covers.iter().all(|i: &CoverHits| i.bin != *bin) {
+                            covers.push(CoverHits::new(bin.clone()));
+                        }
+                        watched
+                            .entry(variable)
+                            .and_modify(|i: &mut Vec<Arc<Statement>>| i.push(run.clone()))
+                            .or_insert_with(|| vec![run.clone()]);
+                        continue;
+                    }
+
+                    if config.engine == Engine::Compiled && is_combinational(&run) {
+                        // binary statements are linked once for every input
+                        if compiled_seen.insert(Arc::as_ptr(&run)) {
+                            compiled.push(run);
+                        }
+                        continue;
+                    }
+
                     // println!("{:?} --> {:?}", variable, run);
                     map.entry(variable)
-                        .and_modify(|i: &mut Vec<Rc<Statement>>| i.push(run.clone()))
+                        .and_modify(|i: &mut Vec<Arc<Statement>>| i.push(run.clone()))
                         .or_insert_with(|| vec![run.clone()]);
                 }
+                Condition::Window {
+                    variable,
+                    from,
+                    to,
+                    kind,
+                    span,
+                } => {
+                    let index = windows.len();
+
+                    pq.push(Reverse(Signal {
+                        time: from,
+                        sequence: order.sequence(),
+                        action: Arc::new(Statement::WindowStart(index)),
+                    }));
+                    pq.push(Reverse(Signal {
+                        time: to,
+                        sequence: order.sequence(),
+                        action: Arc::new(Statement::WindowEnd(index)),
+                    }));
+
+                    let check = Arc::new(Statement::WindowCheck(index));
+                    watched
+                        .entry(variable.clone())
+                        .and_modify(|i: &mut Vec<Arc<Statement>>| i.push(check.clone()))
+                        .or_insert_with(|| vec![check]);
+
+                    windows.push(Window {
+                        variable,
+                        kind,
+                        span,
+                        state: WindowState::default(),
+                    });
+                }
             }
         }
 
-        let vcd = vcd_ast
-            .map(|ast| VcdGenerator::new(&config.vcd_path, max_time.vcd_scale(), ast))
-            .transpose()?;
+        let netlist = if config.engine == Engine::Compiled {
+            Some(Netlist::compile(&compiled)?)
+        } else {
+            None
+        };
+
+        let mut observers: Vec<Box<dyn SimulationObserver>> = Vec::new();
+        if let Some(ast) = vcd_ast {
+            observers.push(Box::new(VcdGenerator::new(
+                &config.vcd_path,
+                &process.name.0,
+                max_time.vcd_scale(),
+                ast,
+                config.vcd_overshoot_duration,
+            )?));
+        }
+        observers.extend(config.observers.iter().map(|i| i.create(&process.name.0)));
+        if !observers.is_empty() {
+            let mut signals: Vec<_> = statements
+                .iter()
+                .filter_map(|i| i.writes())
+                .cloned()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+            signals.sort_by_key(|i| i.identifier);
+
+            // binary statements are linked once for every input
+            let mut seen = HashSet::new();
+            let netlist: Vec<_> = statements
+                .iter()
+                .filter(|i| seen.insert(Arc::as_ptr(i)))
+                .cloned()
+                .collect();
+
+            for i in &mut observers {
+                i.start(&signals);
+                i.netlist(&netlist);
+            }
+        }
 
         Ok(Self {
+            statements,
             pq,
+            order,
             map,
+            netlist,
             store: HashMap::default(),
-            vcd,
+            forced: HashSet::new(),
+            windows,
+            watched,
+            observers,
+            failures: Vec::new(),
+            assertions: 0,
+            covers,
+            covered: HashMap::new(),
+            pending: HashMap::new(),
+            generation: 0,
             config,
             last_instant: Instant::START,
         })
     }
 
     pub fn finalize(&mut self) -> Result<(), SimulationError> {
-        if let Some(ref mut i) = self.vcd {
-            i.finalize(self.last_instant, self.config.vcd_overshoot_duration)?;
+        for i in &mut self.observers {
+            i.finish(self.last_instant)?;
         }
 
         Ok(())
     }
 
-    fn get_var(&self, var: &UniqueVariableRef) -> Option<Value> {
+    pub(crate) fn get_var(&self, var: &UniqueVariableRef) -> Option<Value> {
         self.store.get(var).cloned()
     }
 
-    fn store_var(&mut self, var: &UniqueVariableRef, value: impl Into<Value>) {
-        self.store.insert(var.clone(), value.into());
+    /// Returns whether the stored value changed.
+    fn store_var(&mut self, var: &UniqueVariableRef, value: impl Into<Value>) -> bool {
+        if self.forced.contains(var) {
+            return false;
+        }
+
+        let value = value.into();
+        self.store.insert(var.clone(), value.clone()) != Some(value)
     }
 
-    fn handle_signal<'action>(
-        &mut self,
+    /// Computes the outcome of a builtin, move or set, without storing it.
+    /// Returns `None` when an input has no value yet.
+    fn evaluate<'action>(
+        &self,
         action: &'action Statement,
-    ) -> Result<Vec<&'action UniqueVariableRef>, SimulationError> {
-        let mut modified_variables = Vec::new();
+    ) -> Result<Option<(&'action UniqueVariableRef, Value)>, SimulationError> {
+        macro_rules! get {
+            ($variable: expr) => {
+                if let Some(i) = self.get_var($variable) {
+                    i
+                } else {
+                    return Ok(None);
+                }
+            };
+        }
 
-        macro_rules! update {
-            ($($variable: ident),* -> $result: ident $block: block) => {
-                {
-                    $(
-                        let $variable = if let Some(i) = self.get_var($variable) {
-                            i
-                        } else {
-                            // println!("didn't exist: {:?}", $variable);
-                            return Ok(modified_variables)
-                        };
-                    )*
+        Ok(Some(match action {
+            Statement::Not { input, into } => (into, (!get!(input))?),
+            Statement::And(BinaryBuiltin { a, b, into }) => (into, (get!(a) & get!(b))?),
+            Statement::Or(BinaryBuiltin { a, b, into }) => (into, (get!(a) | get!(b))?),
+            Statement::Nand(BinaryBuiltin { a, b, into }) => (into, (!(get!(a) & get!(b))?)?),
+            Statement::Nor(BinaryBuiltin { a, b, into }) => (into, (!(get!(a) | get!(b))?)?),
+            Statement::Xor(BinaryBuiltin { a, b, into }) => (into, (get!(a) ^ get!(b))?),
+            Statement::Xnor(BinaryBuiltin { a, b, into }) => (into, (!(get!(a) ^ get!(b))?)?),
+            Statement::Move(a, b) => (a, get!(b)),
+            Statement::Set(a, b) => (a, b.into()),
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Schedules the outcome of a delayed statement. With inertial delays,
+    /// a new value replaces the pending one, so pulses shorter than the delay disappear.
+    fn schedule_write(&mut self, into: &UniqueVariableRef, value: Value, time: Instant) {
+        self.generation += 1;
+
+        if self.config.delay_mode == DelayMode::Inertial {
+            if let Some((_, pending)) = self.pending.get(into) {
+                if *pending == value {
+                    return;
+                }
+            }
+
+            self.pending
+                .insert(into.clone(), (self.generation, value.clone()));
+        }
+
+        self.pq.push(Reverse(Signal {
+            sequence: self.order.sequence(),
+            time,
+            action: Arc::new(Statement::Write {
+                into: into.clone(),
+                value,
+                generation: self.generation,
+            }),
+        }));
+    }
 
-                    let res = $block?;
+    /// Creates the error for a failing temporal assertion. After failing once,
+    /// the window isn't watched anymore.
+    fn window_error(&mut self, index: usize, time: Instant, reason: &str) -> SimulationError {
+        self.windows[index].state.active = false;
+        let span = &self.windows[index].span;
+        for i in &mut self.observers {
+            i.assertion(span, false, time);
+        }
+
+        AssertionError {
+            src: span.source().clone().into(),
+            span: span.clone().into(),
+            time,
+            reason: reason.to_string(),
+        }
+        .into()
+    }
+
+    fn check_window(&mut self, index: usize, time: Instant) -> Result<(), SimulationError> {
+        if !self.windows[index].state.active {
+            return Ok(());
+        }
 
-                    modified_variables.push($result);
+        let value = self.get_var(&self.windows[index].variable);
+        let window = &mut self.windows[index];
 
-                    self.store_var($result, res)
+        match window.kind {
+            WindowKind::Always => {
+                if value != Some(Value::Bit(true)) {
+                    return Err(self.window_error(index, time, "became false"));
                 }
-            };
+            }
+            WindowKind::Stable => {
+                if value != window.state.start_value {
+                    return Err(self.window_error(index, time, "changed"));
+                }
+            }
+            WindowKind::Eventually => {
+                if value == Some(Value::Bit(true)) {
+                    window.state.satisfied = true;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_signal<'action>(
+        &mut self,
+        action: &'action Statement,
+        time: Instant,
+    ) -> Result<Vec<&'action UniqueVariableRef>, SimulationError> {
+        let mut modified_variables = Vec::new();
 
+        macro_rules! update {
             ($($variable: ident),* $block: block) => {
                 $(
                     let $variable = if let Some(i) = self.get_var($variable) {
@@ -138,57 +418,112 @@ impl<'config> Simulation<'config> {
         }
 
         match action {
-            Statement::Not { input, into } => {
-                update!(input -> into {
-                    !input
-                });
-            }
-            Statement::And(BinaryBuiltin { a, b, into }) => {
-                update!(a, b -> into {
-                    a & b
-                });
-            }
-            Statement::Or(BinaryBuiltin { a, b, into }) => {
-                update!(a, b -> into {
-                    a | b
-                });
-            }
-            Statement::Nand(BinaryBuiltin { a, b, into }) => {
-                update!(a, b -> into {
-                    !(a & b)?
-                });
-            }
-            Statement::Nor(BinaryBuiltin { a, b, into }) => {
-                update!(a, b -> into {
-                    !(a | b)?
-                });
-            }
-            Statement::Xor(BinaryBuiltin { a, b, into }) => {
-                update!(a, b -> into {
-                    a ^ b
-                });
+            Statement::Not { .. }
+            | Statement::And(_)
+            | Statement::Or(_)
+            | Statement::Nand(_)
+            | Statement::Nor(_)
+            | Statement::Xor(_)
+            | Statement::Xnor(_)
+            | Statement::Move(_, _)
+            | Statement::Set(_, _) => {
+                if let Some((into, value)) = self.evaluate(action)? {
+                    // only real changes propagate, otherwise feedback never settles
+                    if self.store_var(into, value) {
+                        modified_variables.push(into);
+                    }
+                }
             }
-            Statement::Xnor(BinaryBuiltin { a, b, into }) => {
-                update!(a, b -> into {
-                    !(a ^ b)?
-                });
+            Statement::Delayed(statement, delay) => {
+                if let Some((into, value)) = self.evaluate(statement)? {
+                    self.schedule_write(into, value, time.after(delay));
+                }
             }
-            Statement::Move(a, b) => {
-                update!(b -> a { Result::<_, SimulationError>::Ok(b) });
+            Statement::Write {
+                into,
+                value,
+                generation,
+            } => {
+                if self.config.delay_mode == DelayMode::Inertial {
+                    match self.pending.get(into) {
+                        Some((g, _)) if g == generation => {
+                            self.pending.remove(into);
+                        }
+                        // superseded by a later change
+                        _ => return Ok(modified_variables),
+                    }
+                }
+
+                if self.store_var(into, value.clone()) {
+                    modified_variables.push(into);
+                }
             }
-            Statement::Set(a, b) => {
-                update!( -> a { Result::<_, SimulationError>::Ok(b) });
+            Statement::Force(a, b) => {
+                self.forced.insert(a.clone());
+                self.store.insert(a.clone(), b.into());
+                modified_variables.push(a);
             }
             Statement::Assert(v, span) => {
                 update!(v {
-                    if let Value::Bit(true) = v {} else {
+                    self.assertions += 1;
+                    let passed = v == Value::Bit(true);
+                    for i in &mut self.observers {
+                        i.assertion(span, passed, time);
+                    }
+
+                    if !passed {
                         return Err(AssertionError {
                             src: span.source().clone().into(),
-                            span: span.clone().into()
+                            span: span.clone().into(),
+                            time,
+                            reason: "here".to_string(),
                         }.into())
                     }
                 });
             }
+            Statement::Cover(v, bin) => {
+                let hit = self.get_var(v) == Some(Value::Bit(true));
+                // a condition that stays true is hit once
+                let before = self.covered.insert(v.clone(), hit).unwrap_or(false);
+                if hit && !before {
+                    if let Some(i) = self.covers.iter_mut().find(|i| i.bin == *bin) {
+                        i.times.push(time);
+                    }
+                }
+            }
+            Statement::WindowStart(index) => {
+                let window = &mut self.windows[*index];
+                window.state.active = true;
+                window.state.start_value = self.store.get(&window.variable).cloned();
+
+                self.check_window(*index, time)?;
+            }
+            Statement::WindowCheck(index) => {
+                self.check_window(*index, time)?;
+            }
+            Statement::WindowEnd(index) => {
+                // changes in the last step may not have been checked yet
+                self.check_window(*index, time)?;
+
+                self.assertions += 1;
+                let window = &mut self.windows[*index];
+                let active = window.state.active;
+                window.state.active = false;
+
+                if let WindowKind::Eventually = window.kind {
+                    if !window.state.satisfied {
+                        return Err(self.window_error(*index, time, "never became true"));
+                    }
+                }
+
+                // a window that failed before was already reported
+                if active {
+                    let span = &self.windows[*index].span;
+                    for i in &mut self.observers {
+                        i.assertion(span, true, time);
+                    }
+                }
+            }
         }
 
         Ok(modified_variables)
@@ -199,31 +534,264 @@ impl<'config> Simulation<'config> {
         modified_variables: Vec<&UniqueVariableRef>,
         time: Instant,
     ) -> Result<(), SimulationError> {
+        if let Some(ref mut netlist) = self.netlist {
+            for i in &modified_variables {
+                if let (Some(slot), Some(value)) = (netlist.slot_of(i), self.store.get(*i)) {
+                    netlist.set(slot, value);
+                }
+            }
+        }
+
         for i in modified_variables {
-            // println!("{:?} modified variable {:?}", time, i);
-            if let Some(value) = self.get_var(i) {
-                if let Some(ref mut gen) = self.vcd {
-                    gen.update_wire(i, &value, time)?;
+            self.changed(i, time)?;
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the compiled netlist, if any of its inputs changed. Everything that
+    /// changed because of that is handled like any other change.
+    fn settle_netlist(&mut self, time: Instant) -> Result<(), SimulationError> {
+        let mut netlist = match self.netlist.take() {
+            Some(netlist) if netlist.is_dirty() => netlist,
+            netlist => {
+                self.netlist = netlist;
+                return Ok(());
+            }
+        };
+
+        let changed = netlist.propagate(
+            |i| self.forced.contains(i),
+            |statement| {
+                for i in &mut self.observers {
+                    i.statement_evaluated(statement, time);
                 }
+            },
+        );
+        let res = changed.into_iter().try_for_each(|slot| {
+            let variable = &netlist.variables[slot];
+            if let Some(value) = netlist.value(slot) {
+                self.store.insert(variable.clone(), value);
             }
 
-            for statement in self.map.get(i).unwrap_or(&Vec::new()) {
-                self.pq.push(Reverse(Signal {
-                    time: time.add_delta(),
-                    action: statement.clone(),
-                }));
+            self.changed(variable, time)
+        });
+
+        self.netlist = Some(netlist);
+        res
+    }
+
+    /// Tells the observers about a changed variable, and schedules everything that depends on it.
+    fn changed(&mut self, i: &UniqueVariableRef, time: Instant) -> Result<(), SimulationError> {
+        // println!("{:?} modified variable {:?}", time, i);
+        if let Some(value) = self.get_var(i) {
+            for observer in &mut self.observers {
+                observer.value_changed(i, &value, time)?;
             }
         }
 
+        for statement in self.map.get(i).unwrap_or(&Vec::new()) {
+            self.pq.push(Reverse(Signal {
+                sequence: self.order.sequence(),
+                time: time.add_delta(),
+                action: statement.clone(),
+            }));
+        }
+
+        // temporal assertions are checked once the current step has settled
+        for statement in self.watched.get(i).unwrap_or(&Vec::new()) {
+            self.pq.push(Reverse(Signal {
+                sequence: self.order.sequence(),
+                time: time.observe(),
+                action: statement.clone(),
+            }));
+        }
+
         Ok(())
     }
 
+    /// Every non-generated signal written in the oscillating time step.
+    fn oscillation(&self, action: &Statement, time: Instant) -> Oscillation {
+        let mut signals: Vec<_> = self
+            .pq
+            .iter()
+            .filter(|Reverse(i)| i.time.nanos() == time.nanos())
+            .map(|Reverse(i)| &*i.action)
+            .chain(std::iter::once(action))
+            .filter_map(Statement::writes)
+            .filter(|i| !i.generated)
+            .map(UniqueVariableRef::name)
+            .collect();
+        signals.sort();
+        signals.dedup();
+
+        Oscillation {
+            time,
+            signals: signals.join(", "),
+        }
+    }
+
+    /// Runs a statement at the given time.
+    pub(crate) fn schedule(&mut self, time: Instant, action: Statement) {
+        self.pq.push(Reverse(Signal {
+            sequence: self.order.sequence(),
+            time,
+            action: Arc::new(action),
+        }));
+    }
+
+    /// The time of the last event that ran.
+    #[must_use]
+    pub fn last_instant(&self) -> Instant {
+        self.last_instant
+    }
+
+    /// Captures the current state, to return to it later with [`Simulation::restore`].
+    /// Observers like the vcd writer keep what they need to rewind until the snapshot is dropped.
+    #[must_use]
+    pub fn snapshot(&mut self) -> Snapshot {
+        Snapshot {
+            time: self.last_instant,
+            store: self.store.clone(),
+            pq: self.pq.clone(),
+            forced: self.forced.clone(),
+            windows: self.windows.iter().map(|i| i.state.clone()).collect(),
+            pending: self.pending.clone(),
+            generation: self.generation,
+            scheduled: self.order.scheduled,
+            failures: self.failures.len(),
+            covers: self.covers.iter().map(CoverHits::hits).collect(),
+            observers: self.observers.iter_mut().map(|i| i.checkpoint()).collect(),
+        }
+    }
+
+    /// Returns to the state of a snapshot. Assertion failures and vcd changes
+    /// recorded after the snapshot was taken are forgotten.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.last_instant = snapshot.time;
+        self.store = snapshot.store.clone();
+        self.pq = snapshot.pq.clone();
+        self.forced = snapshot.forced.clone();
+        for (window, state) in self.windows.iter_mut().zip(&snapshot.windows) {
+            window.state = state.clone();
+        }
+        self.pending = snapshot.pending.clone();
+        self.generation = snapshot.generation;
+        self.order.scheduled = snapshot.scheduled;
+        self.failures.truncate(snapshot.failures);
+        for (index, i) in self.covers.iter_mut().enumerate() {
+            i.times
+                .truncate(snapshot.covers.get(index).copied().unwrap_or(0));
+        }
+        for (variable, hit) in &mut self.covered {
+            *hit = self.store.get(variable) == Some(&Value::Bit(true));
+        }
+        for (index, i) in self.observers.iter_mut().enumerate() {
+            i.rewind(
+                snapshot
+                    .observers
+                    .get(index)
+                    .map_or(0, Checkpoint::position),
+            );
+        }
+        if let Some(ref mut netlist) = self.netlist {
+            netlist.load(&self.store);
+        }
+    }
+
+    /// The statements a snapshot of this simulation can refer to,
+    /// and the variables of the instantiated circuit.
+    pub(crate) fn snapshot_table(
+        &self,
+        variables: HashMap<usize, UniqueVariableRef>,
+    ) -> SnapshotTable {
+        SnapshotTable {
+            statements: self.statements.clone(),
+            windows: self.windows.len(),
+            variables,
+        }
+    }
+
+    /// Stops telling anything to the vcd writer and observers.
+    #[must_use]
+    pub(crate) fn without_observers(mut self) -> Self {
+        self.observers.clear();
+        self
+    }
+
+    /// The value of every signal that isn't generated by the compiler, by name.
+    /// Instances of the same circuit share names, so the identifier is part of the key.
+    pub(crate) fn final_values(&self) -> BTreeMap<(String, usize), Value> {
+        self.store
+            .iter()
+            .filter(|(k, _)| !k.generated)
+            .map(|(k, v)| ((k.name(), k.identifier), v.clone()))
+            .collect()
+    }
+
+    /// The number of assertions checked so far, including temporal assertions that ended.
+    #[must_use]
+    pub fn assertions(&self) -> usize {
+        self.assertions
+    }
+
+    /// Every cover statement, with the times its condition became true.
+    #[must_use]
+    pub fn covers(&self) -> &[CoverHits] {
+        &self.covers
+    }
+
+    /// Takes all assertion failures collected so far.
+    pub fn take_failures(&mut self) -> Vec<AssertionError> {
+        std::mem::take(&mut self.failures)
+    }
+
+    /// The time of the next event, if there is one.
+    #[must_use]
+    pub fn next_instant(&self) -> Option<Instant> {
+        self.pq.peek().map(|Reverse(signal)| signal.time)
+    }
+
     pub fn step(&mut self) -> Result<SimulationState, SimulationError> {
-        if let Some(Reverse(Signal { time, action })) = self.pq.pop() {
+        if let (Some(limit), Some(next)) = (self.config.simulation_time, self.next_instant()) {
+            if next.nanos() > limit.nanos() {
+                self.finalize()?;
+                return Ok(SimulationState::TimeLimit {
+                    reached: self.last_instant,
+                    pending: self.pq.len(),
+                });
+            }
+        }
+
+        if let Some(Reverse(Signal { time, action, .. })) = self.pq.pop() {
+            if time.deltas() > self.config.max_deltas {
+                return Err(self.oscillation(&action, time).into());
+            }
+
+            if time.nanos() != self.last_instant.nanos() {
+                for i in &mut self.observers {
+                    i.time_advanced(self.last_instant, time);
+                }
+            }
+            for i in &mut self.observers {
+                i.statement_evaluated(&action, time);
+            }
+
             // println!("{:?}", action);
-            let modified_variables = self.handle_signal(&action)?;
+            let modified_variables = match self.handle_signal(&action, time) {
+                Err(SimulationError::AssertionError(e)) if !self.config.fail_fast => {
+                    self.failures.push(e);
+                    Vec::new()
+                }
+                res => res?,
+            };
             self.update_queue(modified_variables, time)?;
 
+            // combinational logic settles once, after every event of this instant is handled
+            if self.next_instant() != Some(time) {
+                self.settle_netlist(time)?;
+            }
+
             self.last_instant = time;
 
             Ok(SimulationState::Continue)
@@ -237,4 +805,9 @@ impl<'config> Simulation<'config> {
 pub enum SimulationState {
     Continue,
     End,
+    /// stopped at the configured simulation time, with events still pending
+    TimeLimit {
+        reached: Instant,
+        pending: usize,
+    },
 }
@@ -0,0 +1,334 @@
+use crate::parse::ast::Constant;
+use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::linked_ast::Statement;
+use crate::sim::observer::Checkpoint;
+use crate::sim::signal::Signal;
+use crate::sim::simulation::WindowState;
+use crate::sim::value::Value;
+use crate::time::{Instant, Region};
+use miette::Diagnostic;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum SnapshotError {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    SnapshotIoError(#[from] SnapshotIoError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    InvalidSnapshot(#[from] InvalidSnapshot),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("couldn't read or write snapshot: {:?}", _0)]
+#[diagnostic()]
+pub struct SnapshotIoError(std::io::Error);
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("invalid snapshot on line {}: {}", line, reason)]
+#[diagnostic(help("snapshots can only be loaded by a simulation of the same program"))]
+pub struct InvalidSnapshot {
+    line: usize,
+    reason: String,
+}
+
+const HEADER: &str = "hithlain snapshot 3";
+
+/// The state of a simulation at one point in time: the value of every variable,
+/// the pending events and the current time.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub(crate) time: Instant,
+    pub(crate) store: HashMap<UniqueVariableRef, Value>,
+    pub(crate) pq: BinaryHeap<Reverse<Signal>>,
+    pub(crate) forced: HashSet<UniqueVariableRef>,
+    pub(crate) windows: Vec<WindowState>,
+    pub(crate) pending: HashMap<UniqueVariableRef, (u64, Value)>,
+    pub(crate) generation: u64,
+    /// how many signals were scheduled, which determines the order of the next ones
+    pub(crate) scheduled: u64,
+
+    /// the number of assertion failures when the snapshot was taken
+    pub(crate) failures: usize,
+    /// how many times every cover was hit when the snapshot was taken
+    pub(crate) covers: Vec<usize>,
+    /// the checkpoint of every observer, like the vcd writer
+    pub(crate) observers: Vec<Checkpoint>,
+}
+
+/// What the statements and variables in a saved snapshot refer to.
+/// Statements are saved as their index in the simulation, variables as their identifier.
+pub(crate) struct SnapshotTable {
+    pub(crate) statements: Vec<Arc<Statement>>,
+    pub(crate) windows: usize,
+    pub(crate) variables: HashMap<usize, UniqueVariableRef>,
+}
+
+fn bit(value: &Value) -> u8 {
+    let Value::Bit(b) = value;
+    u8::from(*b)
+}
+
+fn io(e: std::io::Error) -> SnapshotError {
+    SnapshotIoError(e).into()
+}
+
+impl Snapshot {
+    /// The simulation time at which the snapshot was taken.
+    #[must_use]
+    pub fn time(&self) -> Instant {
+        self.time
+    }
+
+    /// Writes the snapshot as text, one item per line. Assertion failures, cover hits and
+    /// observer checkpoints aren't saved, so a loaded snapshot starts without them.
+    pub(crate) fn write(
+        &self,
+        table: &SnapshotTable,
+        w: &mut dyn Write,
+    ) -> Result<(), SnapshotError> {
+        let instant = |i: Instant| {
+            let region = match i.region() {
+                Region::Active => "active",
+                Region::Observe => "observe",
+            };
+            format!(
+                "{} {} {} {}",
+                i.nanos(),
+                i.process_steps(),
+                region,
+                i.deltas()
+            )
+        };
+
+        writeln!(w, "{}", HEADER).map_err(io)?;
+        writeln!(w, "time {}", instant(self.time)).map_err(io)?;
+        writeln!(w, "generation {}", self.generation).map_err(io)?;
+        writeln!(w, "scheduled {}", self.scheduled).map_err(io)?;
+
+        // sorted, so the same state always gives the same file
+        let mut store: Vec<_> = self.store.iter().collect();
+        store.sort_by_key(|(k, _)| k.identifier);
+        for (k, v) in store {
+            writeln!(w, "value {} {}", k.identifier, bit(v)).map_err(io)?;
+        }
+
+        let mut forced: Vec<_> = self.forced.iter().map(|i| i.identifier).collect();
+        forced.sort_unstable();
+        for i in forced {
+            writeln!(w, "forced {}", i).map_err(io)?;
+        }
+
+        let mut pending: Vec<_> = self.pending.iter().collect();
+        pending.sort_by_key(|(k, _)| k.identifier);
+        for (k, (generation, v)) in pending {
+            writeln!(w, "pending {} {} {}", k.identifier, generation, bit(v)).map_err(io)?;
+        }
+
+        for (index, i) in self.windows.iter().enumerate() {
+            writeln!(
+                w,
+                "window {} {} {} {}",
+                index,
+                u8::from(i.active),
+                i.start_value
+                    .as_ref()
+                    .map_or("-".to_string(), |v| bit(v).to_string()),
+                u8::from(i.satisfied)
+            )
+            .map_err(io)?;
+        }
+
+        let indices: HashMap<_, _> = table
+            .statements
+            .iter()
+            .enumerate()
+            .map(|(index, i)| (Arc::as_ptr(i), index))
+            .collect();
+
+        let mut events: Vec<_> = self.pq.iter().map(|Reverse(i)| i).collect();
+        events.sort_by_key(|i| (i.time, i.sequence));
+        for Signal {
+            time,
+            sequence,
+            action,
+        } in events
+        {
+            let event = if let Some(index) = indices.get(&Arc::as_ptr(action)) {
+                format!("statement {}", index)
+            } else {
+                match &**action {
+                    Statement::Set(v, Constant::Bit(b)) => {
+                        format!("set {} {}", v.identifier, u8::from(*b))
+                    }
+                    Statement::Write {
+                        into,
+                        value,
+                        generation,
+                    } => format!("write {} {} {}", into.identifier, bit(value), generation),
+                    Statement::WindowStart(i) => format!("window-start {}", i),
+                    Statement::WindowCheck(i) => format!("window-check {}", i),
+                    Statement::WindowEnd(i) => format!("window-end {}", i),
+                    _ => unreachable!("all other statements are part of the simulation"),
+                }
+            };
+
+            writeln!(w, "event {} {} {}", instant(*time), sequence, event).map_err(io)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn read(table: &SnapshotTable, r: &mut dyn BufRead) -> Result<Self, SnapshotError> {
+        let mut res = Snapshot {
+            time: Instant::START,
+            store: HashMap::new(),
+            pq: BinaryHeap::new(),
+            forced: HashSet::new(),
+            windows: vec![WindowState::default(); table.windows],
+            pending: HashMap::new(),
+            generation: 0,
+            scheduled: 0,
+            failures: 0,
+            covers: Vec::new(),
+            observers: Vec::new(),
+        };
+
+        let mut lines = r.lines().enumerate().peekable();
+        if lines.peek().is_none() {
+            return Err(InvalidSnapshot {
+                line: 1,
+                reason: "empty file".to_string(),
+            }
+            .into());
+        }
+
+        for (index, line) in lines {
+            let line = line.map_err(io)?;
+            let line_number = index + 1;
+            let invalid = |reason: &str| -> SnapshotError {
+                InvalidSnapshot {
+                    line: line_number,
+                    reason: reason.to_string(),
+                }
+                .into()
+            };
+
+            if index == 0 {
+                if line != HEADER {
+                    return Err(invalid("not a hithlain snapshot"));
+                }
+                continue;
+            }
+
+            let words: Vec<_> = line.split_whitespace().collect();
+            let number = |i: usize| -> Result<u64, SnapshotError> {
+                words
+                    .get(i)
+                    .and_then(|w| w.parse().ok())
+                    .ok_or_else(|| invalid("expected a number"))
+            };
+            let value = |i: usize| -> Result<Value, SnapshotError> {
+                match words.get(i) {
+                    Some(&"0") => Ok(Value::Bit(false)),
+                    Some(&"1") => Ok(Value::Bit(true)),
+                    _ => Err(invalid("expected a value")),
+                }
+            };
+            let variable = |i: usize| -> Result<UniqueVariableRef, SnapshotError> {
+                let identifier = number(i)?;
+                usize::try_from(identifier)
+                    .ok()
+                    .and_then(|i| table.variables.get(&i))
+                    .cloned()
+                    .ok_or_else(|| invalid(&format!("unknown variable {}", identifier)))
+            };
+            let window = |i: usize| -> Result<usize, SnapshotError> {
+                usize::try_from(number(i)?)
+                    .ok()
+                    .filter(|i| *i < table.windows)
+                    .ok_or_else(|| invalid("unknown window"))
+            };
+            let instant = |i: usize| -> Result<Instant, SnapshotError> {
+                let region = match words.get(i + 2) {
+                    Some(&"active") => Region::Active,
+                    Some(&"observe") => Region::Observe,
+                    _ => return Err(invalid("expected a region")),
+                };
+                Ok(Instant::from_parts(
+                    number(i)?,
+                    number(i + 1)?,
+                    region,
+                    number(i + 3)?,
+                ))
+            };
+
+            match words.first() {
+                Some(&"time") => res.time = instant(1)?,
+                Some(&"generation") => res.generation = number(1)?,
+                Some(&"scheduled") => res.scheduled = number(1)?,
+                Some(&"value") => {
+                    res.store.insert(variable(1)?, value(2)?);
+                }
+                Some(&"forced") => {
+                    res.forced.insert(variable(1)?);
+                }
+                Some(&"pending") => {
+                    res.pending.insert(variable(1)?, (number(2)?, value(3)?));
+                }
+                Some(&"window") => {
+                    let start_value = if words.get(3) == Some(&"-") {
+                        None
+                    } else {
+                        Some(value(3)?)
+                    };
+
+                    res.windows[window(1)?] = WindowState {
+                        active: number(2)? == 1,
+                        start_value,
+                        satisfied: number(4)? == 1,
+                    };
+                }
+                Some(&"event") => {
+                    let time = instant(1)?;
+                    let sequence = number(5)?;
+                    let action = match words.get(6) {
+                        Some(&"statement") => usize::try_from(number(7)?)
+                            .ok()
+                            .and_then(|i| table.statements.get(i))
+                            .cloned()
+                            .ok_or_else(|| invalid("unknown statement"))?,
+                        Some(&"set") => {
+                            let Value::Bit(b) = value(8)?;
+                            Arc::new(Statement::Set(variable(7)?, Constant::Bit(b)))
+                        }
+                        Some(&"write") => Arc::new(Statement::Write {
+                            into: variable(7)?,
+                            value: value(8)?,
+                            generation: number(9)?,
+                        }),
+                        Some(&"window-start") => Arc::new(Statement::WindowStart(window(7)?)),
+                        Some(&"window-check") => Arc::new(Statement::WindowCheck(window(7)?)),
+                        Some(&"window-end") => Arc::new(Statement::WindowEnd(window(7)?)),
+                        _ => return Err(invalid("unknown event")),
+                    };
+
+                    res.pq.push(Reverse(Signal {
+                        time,
+                        sequence,
+                        action,
+                    }));
+                }
+                None => {}
+                Some(_) => return Err(invalid("unknown item")),
+            }
+        }
+
+        Ok(res)
+    }
+}
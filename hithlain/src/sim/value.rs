@@ -24,7 +24,7 @@ pub struct TypeMismatch {
     span: SourceSpan,
 }
 
-#[derive(From, Debug, Clone)]
+#[derive(From, Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     Bit(bool),
 }
@@ -33,7 +33,7 @@ impl From<Constant> for Value {
     fn from(c: Constant) -> Self {
         match c {
             Constant::Bit(n) => Value::Bit(n),
-            _ => todo!()
+            _ => todo!(),
         }
     }
 }
@@ -42,7 +42,7 @@ impl From<&Constant> for Value {
     fn from(c: &Constant) -> Self {
         match c.clone() {
             Constant::Bit(n) => Value::Bit(n),
-            _ => todo!()
+            _ => todo!(),
         }
     }
 }
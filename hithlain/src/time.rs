@@ -59,10 +59,22 @@ pub fn parse_timespec(spec: &str) -> Result<Duration, TimespecError> {
     Err(TimespecError::InvalidSuffix(spec.to_string()))
 }
 
+/// The phases of a single process step. Everything in one region has run before the next one
+/// starts, however many delta cycles that takes. Assertions used to run in the next process
+/// step, which only came after the logic settled as long as nothing wrote in that step.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Ord, PartialOrd)]
+pub enum Region {
+    /// inputs are applied, and combinational logic settles through delta cycles
+    Active,
+    /// assertions look at the settled values. nothing is written here.
+    Observe,
+}
+
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Ord, PartialOrd)]
 pub struct Instant {
     nanos: u64,
     process_steps: u64,
+    region: Region,
     deltas: u64,
 }
 
@@ -74,25 +86,21 @@ impl Instant {
         Self {
             nanos,
             process_steps: 0,
+            region: Region::Active,
             deltas: 0,
         }
     }
-    #[must_use]
 
+    #[must_use]
     pub fn after(&self, d: &Duration) -> Instant {
-        Instant {
-            nanos: self.nanos + d.nanos,
-            process_steps: 0,
-            deltas: 0,
-        }
+        Instant::nanos_from_start(self.nanos + d.nanos)
     }
-    #[must_use]
 
+    #[must_use]
     pub fn add_delta(&self) -> Instant {
         Self {
-            nanos: self.nanos,
-            process_steps: self.process_steps,
             deltas: self.deltas + 1,
+            ..*self
         }
     }
 
@@ -101,15 +109,51 @@ impl Instant {
         Self {
             nanos: self.nanos,
             process_steps: self.process_steps + 1,
+            region: Region::Active,
             deltas: 0,
         }
     }
 
+    /// The observe region of this process step, after all of its delta cycles.
+    #[must_use]
+    pub fn observe(&self) -> Instant {
+        Self {
+            region: Region::Observe,
+            deltas: 0,
+            ..*self
+        }
+    }
+
     #[must_use]
     pub fn nanos(&self) -> u64 {
         self.nanos
     }
 
+    #[must_use]
+    pub fn deltas(&self) -> u64 {
+        self.deltas
+    }
+
+    #[must_use]
+    pub fn process_steps(&self) -> u64 {
+        self.process_steps
+    }
+
+    #[must_use]
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    #[must_use]
+    pub(crate) fn from_parts(nanos: u64, process_steps: u64, region: Region, deltas: u64) -> Self {
+        Self {
+            nanos,
+            process_steps,
+            region,
+            deltas,
+        }
+    }
+
     #[must_use]
     #[allow(clippy::unused_self)]
     pub fn vcd_scale(&self) -> TimescaleUnit {
@@ -123,3 +167,23 @@ impl Instant {
         // }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Instant;
+
+    #[test]
+    fn regions() {
+        let start = Instant::nanos_from_start(5);
+        let mut settling = start;
+        for _ in 0..10_000 {
+            settling = settling.add_delta();
+        }
+
+        assert!(start < settling);
+        assert!(settling < start.observe());
+        assert_eq!(settling.observe(), start.observe());
+        assert!(start.observe() < start.add_process_step());
+        assert!(start.add_process_step().observe() < Instant::nanos_from_start(6));
+    }
+}
@@ -2,9 +2,12 @@ pub mod vcd_ast;
 
 use crate::sim::config::{FileNotFound, VcdPath};
 use crate::sim::instantiate::UniqueVariableRef;
+use crate::sim::observer::{Checkpoint, SimulationObserver};
+use crate::sim::SimulationError;
 use miette::Diagnostic;
 use std::collections::HashMap;
 use std::io::Write;
+use std::sync::Weak;
 use thiserror::Error;
 use vcd::{IdCode, TimescaleUnit, Writer};
 
@@ -44,15 +47,29 @@ pub struct VcdGenerator {
     writer: Writer<Box<dyn Write>>,
 
     variable_mapping: HashMap<UniqueVariableRef, IdCode>,
+
+    /// the number of changes written to the file
+    written: usize,
+    /// the timestamp of the last change written to the file
+    last_written: Option<u64>,
+    /// the checkpoints of snapshots that may still be restored. While any is alive,
+    /// changes are kept in `changes` instead, so the simulation can go back in time.
+    checkpoints: Vec<Weak<()>>,
+    changes: Vec<(u64, IdCode, bool)>,
+    /// how long the file continues after the last event
+    overshoot: Duration,
 }
 
 impl VcdGenerator {
+    #[allow(clippy::mutable_key_type)]
     pub fn new(
         path: &VcdPath,
+        name: &str,
         timescale: TimescaleUnit,
         toplevel: VcdModule,
+        overshoot: Duration,
     ) -> Result<Self, VcdError> {
-        let w = path.writer()?;
+        let w = path.writer(name)?;
         let mut writer = Writer::new(w);
 
         writer
@@ -76,9 +93,15 @@ impl VcdGenerator {
         Ok(Self {
             writer,
             variable_mapping,
+            written: 0,
+            last_written: None,
+            checkpoints: Vec::new(),
+            changes: Vec::new(),
+            overshoot,
         })
     }
 
+    #[allow(clippy::mutable_key_type)]
     fn write_modules(
         writer: &mut Writer<Box<dyn Write>>,
         variable_mapping: &mut HashMap<UniqueVariableRef, IdCode>,
@@ -126,23 +149,107 @@ impl VcdGenerator {
                     return Ok(());
                 };
 
-                self.writer
-                    .timestamp(time.nanos())
-                    .map_err(FileWriteError)?;
-                self.writer
-                    .change_scalar(*wire, if *b { vcd::Value::V1 } else { vcd::Value::V0 })
-                    .map_err(FileWriteError)?;
+                let wire = *wire;
+                if self.buffering() {
+                    self.changes.push((time.nanos(), wire, *b));
+                } else {
+                    self.flush()?;
+                    self.write_change(time.nanos(), wire, *b)?;
+                }
             }
         }
 
         Ok(())
     }
 
-    pub fn finalize(&mut self, time: Instant, overshoot: Duration) -> Result<(), VcdError> {
+    fn write_change(&mut self, nanos: u64, wire: IdCode, value: bool) -> Result<(), VcdError> {
+        if self.last_written != Some(nanos) {
+            self.writer.timestamp(nanos).map_err(FileWriteError)?;
+            self.last_written = Some(nanos);
+        }
+
         self.writer
-            .timestamp(time.nanos() + overshoot.nanos())
+            .change_scalar(
+                wire,
+                if value {
+                    vcd::Value::V1
+                } else {
+                    vcd::Value::V0
+                },
+            )
             .map_err(FileWriteError)?;
+        self.written += 1;
+
+        Ok(())
+    }
+
+    /// Whether a snapshot that may be restored is still alive.
+    fn buffering(&mut self) -> bool {
+        self.checkpoints.retain(|i| i.strong_count() > 0);
+        !self.checkpoints.is_empty()
+    }
+
+    /// Writes the changes that were kept in memory.
+    fn flush(&mut self) -> Result<(), VcdError> {
+        for (nanos, wire, value) in std::mem::take(&mut self.changes) {
+            self.write_change(nanos, wire, value)?;
+        }
 
         Ok(())
     }
+
+    /// The number of changes recorded so far. Keeps changes in memory until the
+    /// checkpoint is dropped, so they can be forgotten with [`VcdGenerator::truncate`].
+    #[must_use]
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let res = Checkpoint::new(self.written + self.changes.len());
+        self.checkpoints.push(res.watch());
+        res
+    }
+
+    /// Forgets every change after the first `len`. Changes that were
+    /// already written to the file stay.
+    pub fn truncate(&mut self, len: usize) {
+        self.changes.truncate(len.saturating_sub(self.written));
+    }
+
+    pub fn finalize(&mut self, time: Instant) -> Result<(), VcdError> {
+        self.flush()?;
+
+        self.writer
+            .timestamp(time.nanos() + self.overshoot.nanos())
+            .map_err(FileWriteError)?;
+
+        Ok(())
+    }
+}
+
+impl Drop for VcdGenerator {
+    fn drop(&mut self) {
+        // simulations that stop with an error, or are never finished, still keep their changes
+        let _ = self.flush();
+    }
+}
+
+impl SimulationObserver for VcdGenerator {
+    fn value_changed(
+        &mut self,
+        variable: &UniqueVariableRef,
+        value: &Value,
+        time: Instant,
+    ) -> Result<(), SimulationError> {
+        Ok(self.update_wire(variable, value, time)?)
+    }
+
+    fn finish(&mut self, time: Instant) -> Result<(), SimulationError> {
+        Ok(self.finalize(time)?)
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        VcdGenerator::checkpoint(self)
+    }
+
+    fn rewind(&mut self, checkpoint: usize) {
+        self.truncate(checkpoint);
+    }
 }
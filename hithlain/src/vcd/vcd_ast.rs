@@ -10,6 +10,7 @@ pub struct VcdModule {
 }
 
 #[must_use]
+#[allow(clippy::mutable_key_type)]
 pub fn process_to_vcd_ast(process: &Process) -> VcdModule {
     let mut submodules = Vec::new();
     let mut variables = HashSet::new();
@@ -35,7 +36,7 @@ pub fn process_to_vcd_ast(process: &Process) -> VcdModule {
                 i.original
                     .path
                     .last()
-                    .map_or(false, |i| i.name() == &process.name)
+                    .is_some_and(|i| i.name() == &process.name)
             })
             .collect(),
         submodules,
@@ -43,6 +44,7 @@ pub fn process_to_vcd_ast(process: &Process) -> VcdModule {
 }
 
 #[must_use]
+#[allow(clippy::mutable_key_type)]
 pub fn circuit_to_vcd_ast(circuit: &Circuit) -> VcdModule {
     let mut submodules = Vec::new();
     let mut variables = HashSet::new();
@@ -68,13 +70,14 @@ pub fn circuit_to_vcd_ast(circuit: &Circuit) -> VcdModule {
                 i.original
                     .path
                     .last()
-                    .map_or(false, |i| i.name() == &circuit.name)
+                    .is_some_and(|i| i.name() == &circuit.name)
             })
             .collect(),
         submodules,
     }
 }
 
+#[allow(clippy::mutable_key_type)]
 fn analyze_timed_block(
     t: &TimedBlock,
     variables: &mut HashSet<UniqueVariableRef>,
@@ -85,13 +88,14 @@ fn analyze_timed_block(
     }
 }
 
+#[allow(clippy::mutable_key_type)]
 fn analyze_statement(
     s: &Statement,
     variables: &mut HashSet<UniqueVariableRef>,
     submodules: &mut Vec<VcdModule>,
 ) {
     match s {
-        Statement::Assert(v, _) => {
+        Statement::Assert(v, _) | Statement::TemporalAssert(v, _, _) | Statement::Cover(v, _) => {
             variables.insert(v.clone());
         }
         Statement::Not { input, into } => {
@@ -113,11 +117,12 @@ fn analyze_statement(
             variables.insert(a.clone());
             variables.insert(b.clone());
         }
-        Statement::Set(a, _) => {
+        Statement::Set(a, _) | Statement::Force(a, _) => {
             variables.insert(a.clone());
         }
         Statement::CreateCircuitInstance(a) => {
             submodules.push(circuit_to_vcd_ast(a));
         }
+        Statement::Delayed(s, _) => analyze_statement(s, variables, submodules),
     }
 }